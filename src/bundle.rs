@@ -0,0 +1,300 @@
+use std::fs::read_dir;
+use std::path::Path;
+
+use log::*;
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::file_context::{FileContext, FileEnumeration};
+use crate::naming_pattern::NamingPattern;
+use crate::platform_specific::PLATFORM_SEPARATOR_SLASH;
+use crate::prompt::Prompter;
+use crate::suggestion::did_you_mean;
+use crate::symbol_replacer::{check_condition, replace_symbols, replace_symbols_in_text, PARTIAL_DIRECTORY};
+use crate::template_file_list::TemplateFile;
+use crate::template_manifest::*;
+
+// Evaluated before the rest of a bundle, anything it defines with DEFINE_TEMPLATE_VAR can be used in
+//  every file of the bundle. It doesn't create a file itself.
+pub const BUNDLE_SHARED_TEMPLATE: &str = "_shared";
+
+// A directory in the templates directory that creates a whole set of files at once, ie: templates/qt_component
+//  with a header, a source file, a qml file and a qmldir. Every file in it is a template, and the names of
+//  the files and directories in it can have tokens in them too, ie: []FILE_NAME_AS_TYPE[].h
+#[derive(Debug)]
+pub struct Bundle {
+    pub name: String,
+    pub path: String,
+    pub templates: Vec<BundleTemplate>,
+    pub shared_template: Option<TemplateFile>,
+    pub manifest: Option<TemplateManifest>
+}
+
+// Each file a bundle creates along with the template that creates it. 
+pub type BundleFiles<'b> = Vec<(FileContext, &'b TemplateFile)>;
+
+#[derive(Debug)]
+pub struct BundleTemplate {
+    // Where the template is in the bundle, seperated with a / and with its tokens not evaluated yet.
+    pub relative_path: String,
+    pub template: TemplateFile
+}
+
+impl Bundle {
+    pub fn load(template_dir_path: &str, name: &str) -> Result<Self, String> {
+        let name = match find_bundle_by_alias(template_dir_path, name) {
+            Some(aliased_name) => aliased_name,
+            None => name.to_string()
+        };
+
+        let path = template_dir_path.to_string() + PLATFORM_SEPARATOR_SLASH + &name;
+        if name == PARTIAL_DIRECTORY {
+            return Err(format!("{:} is where partials go, it can't be used as a template bundle. ", path));
+        }
+        if !Path::new(&path).is_dir() {
+            let bundle_names = bundle_names(template_dir_path);
+            let suggestion = match did_you_mean(&name, &bundle_names) {
+                Some(suggestion) => format!(" Did you mean {:}?", suggestion),
+                None => String::new()
+            };
+            return Err(format!("There is no template bundle named {:} in {:}.{:}", name, template_dir_path, suggestion));
+        }
+
+        let mut relative_paths: Vec<String> = Vec::new();
+        find_bundle_files(Path::new(&path), "", &mut relative_paths)?;
+
+        let mut templates: Vec<BundleTemplate> = Vec::new();
+        let mut shared_template: Option<TemplateFile> = None;
+        for relative_path in relative_paths {
+            if relative_path == BUNDLE_MANIFEST || relative_path.ends_with(MANIFEST_EXTENSION) {
+                continue;
+            }
+
+            let template = TemplateFile::load(&relative_path, &path, "")
+                .ok_or(format!("Failed to load {:} from template bundle {:}. ", relative_path, name))?;
+
+            if relative_path == BUNDLE_SHARED_TEMPLATE {
+                shared_template = Some(template);
+            } else {
+                templates.push(BundleTemplate { relative_path, template });
+            }
+        }
+
+        if templates.is_empty() {
+            return Err(format!("Template bundle {:} has no templates in it. ", name));
+        }
+
+        let manifest = TemplateManifest::load(&(path.clone() + PLATFORM_SEPARATOR_SLASH + BUNDLE_MANIFEST))?;
+        if let Some(manifest) = &manifest {
+            let relative_paths: Vec<String> = templates.iter().map(|bundle_template| bundle_template.relative_path.clone()).collect();
+            manifest.check_outputs(&relative_paths).map_err(|e| format!("{:} {:}", path, e))?;
+        }
+
+        Ok(Self {
+            name,
+            path,
+            templates,
+            shared_template,
+            manifest
+        })
+    }
+
+    // Every file the bundle creates for one of the files described on the command line, along with the template
+    //  that creates it. Also gives back how many errors there were evaluating the _shared template and file names.
+    pub fn expand(
+        &self,
+        file: &FileContext,
+        harvest_location: &Option<String>,
+        data: Option<&Value>,
+        config: &Config,
+        prompter: &mut Prompter)
+    -> Result<(BundleFiles<'_>, usize), String> {
+
+        let mut error_count: usize = 0;
+
+        // The conditions are checked first so that _shared isn't evaluated for a file nothing gets created for. 
+        let mut created_templates: Vec<&BundleTemplate> = Vec::new();
+        for bundle_template in &self.templates {
+            if !self.creates(&bundle_template.relative_path, file, data, config)? {
+                info!("Skipping {:} from template bundle {:}, the condition for it in the manifest isn't met. ", bundle_template.relative_path, self.name);
+                continue;
+            }
+            created_templates.push(bundle_template);
+        }
+
+        if created_templates.is_empty() {
+            return Ok((Vec::new(), error_count));
+        }
+
+        let mut shared_file = file.clone();
+        if let Some(shared_template) = &self.shared_template {
+            let evaluated_shared_template = replace_symbols(shared_template, file, harvest_location, data, config, prompter);
+            error_count += evaluated_shared_template.diagnostics.len();
+            shared_file.variables.extend(evaluated_shared_template.variables);
+        }
+
+        // Names are evaluated without the enumerations, the naming pattern adds them the same as it does for any other file. 
+        let mut unenumerated_file = shared_file.clone();
+        unenumerated_file.enumerations = FileEnumeration::blank();
+
+        let mut bundle_files: BundleFiles = Vec::new();
+        for bundle_template in created_templates {
+            let source_path = self.path.clone() + PLATFORM_SEPARATOR_SLASH + &bundle_template.relative_path;
+            let evaluated_path = replace_symbols_in_text(&source_path, &bundle_template.relative_path, &unenumerated_file, data, config, prompter);
+            error_count += evaluated_path.diagnostics.len();
+
+            let (directory, file_name) = match evaluated_path.text.rsplit_once('/') {
+                Some((directory, file_name)) => (Some(directory.to_string()), file_name.to_string()),
+                None => (None, evaluated_path.text.clone())
+            };
+            if file_name.is_empty() {
+                return Err(format!("The name of {:} in template bundle {:} evaluated to nothing. ", bundle_template.relative_path, self.name));
+            }
+
+            // Files that start with a dot, like .gitignore, don't have an extension.
+            let (name, extension) = match file_name.split_once('.') {
+                Some((name, extension)) if !name.is_empty() => (name.to_string(), extension.to_string()),
+                _ => (file_name.clone(), String::new())
+            };
+
+            let naming_pattern = NamingPattern::for_template(&bundle_template.template, config)?;
+
+            let mut bundle_file = shared_file.clone();
+            bundle_file.name = name;
+            bundle_file.extension = extension;
+            bundle_file.naming_pattern = match directory {
+                Some(directory) => naming_pattern.in_directory(&directory),
+                None => naming_pattern
+            };
+
+            bundle_files.push((bundle_file, &bundle_template.template));
+        }
+
+        Ok((bundle_files, error_count))
+    }
+
+    // Files only have to be created when the condition for them in the manifest holds, if there is one. 
+    fn creates(&self, relative_path: &str, file: &FileContext, data: Option<&Value>, config: &Config) -> Result<bool, String> {
+        let condition = match self.manifest.as_ref().and_then(|manifest| manifest.output_condition(relative_path)) {
+            Some(condition) => condition,
+            None => return Ok(true)
+        };
+
+        check_condition(condition, file, data, config)
+            .map_err(|e| format!("Failed to check the condition for {:} in the manifest of template bundle {:}. {:}", relative_path, self.name, e))
+    }
+}
+
+// Every file in the bundle, in a set order so that files are always created in the same order.
+fn find_bundle_files(directory: &Path, relative_directory: &str, relative_paths: &mut Vec<String>) -> Result<(), String> {
+    let entries = read_dir(directory).map_err(|e| format!("Failed to read template bundle directory {:}. {:}", directory.display(), e))?;
+
+    let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let relative_path = relative_directory.to_string() + &entry.file_name().to_string_lossy();
+        if entry.path().is_dir() {
+            find_bundle_files(&entry.path(), &(relative_path + "/"), relative_paths)?;
+        } else {
+            relative_paths.push(relative_path);
+        }
+    }
+
+    Ok(())
+}
+
+// A bundle whose manifest lists name as one of its aliases. 
+fn find_bundle_by_alias(template_dir_path: &str, name: &str) -> Option<String> {
+    find_templates(template_dir_path)
+        .into_iter()
+        .find(|(template_name, manifest)| template_name.ends_with('/') && manifest.as_ref().is_some_and(|manifest| manifest.has_alias(name)))
+        .map(|(template_name, _)| template_name.trim_end_matches('/').to_string())
+}
+
+fn bundle_names(template_dir_path: &str) -> Vec<String> {
+    match read_dir(template_dir_path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir() && entry.file_name() != PARTIAL_DIRECTORY)
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect(),
+        Err(_) => Vec::new()
+    }
+}
+
+#[test]
+fn bundle_creates_every_file_in_it() {
+    use std::collections::HashMap;
+
+    let bundle_dir = std::env::temp_dir().join(format!("tt_bundle_test_{:}", std::process::id())).join("component");
+    std::fs::create_dir_all(bundle_dir.join("[]FILE_NAME_WITHOUT_EXTENSION[]")).unwrap();
+    std::fs::write(bundle_dir.join(BUNDLE_SHARED_TEMPLATE), "[]DEFINE_TEMPLATE_VAR{TYPE|||[]FILE_NAME_AS_TYPE[]Item}[]").unwrap();
+    std::fs::write(bundle_dir.join("[]FILE_NAME_AS_TYPE[].h"), "class []TEMPLATE_VAR{TYPE}[];").unwrap();
+    std::fs::write(bundle_dir.join("[]FILE_NAME_WITHOUT_EXTENSION[]").join("qmldir"), "[]TEMPLATE_VAR{TYPE}[] []FILE_NAME[]").unwrap();
+    std::fs::write(bundle_dir.join("[]FILE_NAME_WITHOUT_EXTENSION[]").join(".gitignore"), "").unwrap();
+    let template_dir_path = bundle_dir.parent().unwrap().to_str().unwrap().to_string();
+
+    let bundle = Bundle::load(&template_dir_path, "component").unwrap();
+    assert!(bundle.shared_template.is_some());
+    assert!(Bundle::load(&template_dir_path, "componnet").unwrap_err().ends_with("Did you mean component?"));
+
+    let mut file = FileContext::blank();
+    file.name = "button".to_string();
+
+    let mut prompter = Prompter::non_interactive(HashMap::new());
+    let (bundle_files, error_count) = bundle.expand(&file, &None, None, &Config::default(), &mut prompter).unwrap();
+    assert_eq!(0, error_count);
+
+    let output_paths: Vec<String> = bundle_files.iter().map(|(bundle_file, _)| bundle_file.output_path()).collect();
+    assert_eq!(vec!["Button.h", "button/.gitignore", "button/qmldir"], output_paths);
+
+    let evaluated_files: Vec<String> = bundle_files
+        .iter()
+        .map(|(bundle_file, template)| replace_symbols(template, bundle_file, &None, None, &Config::default(), &mut prompter).text)
+        .collect();
+    assert_eq!("class ButtonItem;", evaluated_files[0]);
+    assert_eq!("ButtonItem qmldir", evaluated_files[2]);
+
+    let _ = std::fs::remove_dir_all(bundle_dir.parent().unwrap());
+}
+
+#[test]
+fn shared_is_only_evaluated_for_files_the_bundle_creates() {
+    use std::collections::HashMap;
+    use crate::config::PLATFORM_AXIS;
+
+    let bundle_dir = std::env::temp_dir().join(format!("tt_conditional_bundle_test_{:}", std::process::id())).join("linux_only");
+    std::fs::create_dir_all(&bundle_dir).unwrap();
+    std::fs::write(bundle_dir.join(BUNDLE_SHARED_TEMPLATE), "[]TEMPLATE_VAR{MISSING}[]").unwrap();
+    std::fs::write(bundle_dir.join("[]FILE_NAME[]"), "").unwrap();
+    std::fs::write(bundle_dir.join(BUNDLE_MANIFEST), r#"{ "outputs": [ { "file": "[]FILE_NAME[]", "if": "PLATFORM == linux" } ] }"#).unwrap();
+    let template_dir_path = bundle_dir.parent().unwrap().to_str().unwrap().to_string();
+
+    let bundle = Bundle::load(&template_dir_path, "linux_only").unwrap();
+    let mut prompter = Prompter::non_interactive(HashMap::new());
+
+    let mut file = FileContext::blank();
+    file.name = "button".to_string();
+    file.enumerations.set(PLATFORM_AXIS, "windows");
+    assert_eq!(0, bundle.expand(&file, &None, None, &Config::default(), &mut prompter).unwrap().1);
+
+    file.enumerations.set(PLATFORM_AXIS, "linux");
+    assert_eq!(1, bundle.expand(&file, &None, None, &Config::default(), &mut prompter).unwrap().1);
+
+    let _ = std::fs::remove_dir_all(bundle_dir.parent().unwrap());
+}
+
+#[test]
+fn partials_are_not_a_bundle() {
+    let template_dir = std::env::temp_dir().join(format!("tt_partials_test_{:}", std::process::id()));
+    std::fs::create_dir_all(template_dir.join(PARTIAL_DIRECTORY)).unwrap();
+    std::fs::write(template_dir.join(PARTIAL_DIRECTORY).join("getter.partial"), "[]TEMPLATE_VAR{name}[]").unwrap();
+    let template_dir_path = template_dir.to_str().unwrap().to_string();
+
+    assert!(Bundle::load(&template_dir_path, PARTIAL_DIRECTORY).is_err());
+    assert!(bundle_names(&template_dir_path).is_empty());
+    assert!(find_templates(&template_dir_path).is_empty());
+
+    let _ = std::fs::remove_dir_all(template_dir);
+}
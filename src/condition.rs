@@ -0,0 +1,165 @@
+use log::*;
+
+// Conditions are the first parameter of the IF token, for example
+//  []IF{PLATFORM == windows|||...|||...}[]. A condition is made up of a
+//  subject, which is a variable name with an optional {argument}, and an
+//  optional comparison against a comma separated list of values. A subject
+//  on its own is true when it has a value.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Condition {
+    pub subject: ConditionSubject,
+    pub comparison: Comparison
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ConditionSubject {
+    pub id: String,
+    pub argument: Option<String>
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Comparison {
+    IsSet,
+    IsNotSet,
+    Equals(Vec<String>),
+    NotEquals(Vec<String>)
+}
+
+impl Condition {
+    pub fn from_string(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+
+        if text.is_empty() {
+            return Err("Condition is empty. ".to_string());
+        }
+
+        let (subject_text, comparison) =
+            if let Some(index) = text.find("==") {
+                (&text[..index], Comparison::Equals(split_value_list(&text[index + 2..])))
+            } else if let Some(index) = text.find("!=") {
+                (&text[..index], Comparison::NotEquals(split_value_list(&text[index + 2..])))
+            } else if let Some(negated_subject) = text.strip_prefix('!') {
+                (negated_subject, Comparison::IsNotSet)
+            } else {
+                (text, Comparison::IsSet)
+            };
+
+        let subject = ConditionSubject::from_string(subject_text)?;
+
+        info!("Parsed condition {:?} {:?}", subject, comparison);
+
+        Ok(Self {
+            subject,
+            comparison
+        })
+    }
+
+    // Gives back the condition with its argument and values changed by f, the subject's name is left as is. 
+    pub fn map_operands<F: Fn(&str) -> String>(self, f: F) -> Self {
+        let comparison = match self.comparison {
+            Comparison::Equals(values)    => Comparison::Equals(values.iter().map(|value| f(value)).collect()),
+            Comparison::NotEquals(values) => Comparison::NotEquals(values.iter().map(|value| f(value)).collect()),
+            comparison => comparison
+        };
+
+        Self {
+            subject: ConditionSubject {
+                id: self.subject.id,
+                argument: self.subject.argument.map(|argument| f(&argument))
+            },
+            comparison
+        }
+    }
+
+    // subject_value is what the subject evaluated to for the file being
+    //  generated, None meaning that the subject isn't set at all.
+    pub fn evaluate(&self, subject_value: &Option<String>) -> bool {
+        match (&self.comparison, subject_value) {
+            (Comparison::IsSet,    value) => value.is_some(),
+            (Comparison::IsNotSet, value) => value.is_none(),
+            (Comparison::Equals(values),    Some(value)) => values.contains(value),
+            (Comparison::Equals(_),         None)        => false,
+            (Comparison::NotEquals(values), Some(value)) => !values.contains(value),
+            (Comparison::NotEquals(_),      None)        => true
+        }
+    }
+}
+
+impl ConditionSubject {
+    fn from_string(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+
+        let (id, argument) = match text.find('{') {
+            Some(index) => {
+                if !text.ends_with('}') {
+                    return Err(format!("Condition subject {:} is missing a closing }}", text));
+                }
+                (&text[..index], Some(text[index + 1..text.len() - 1].trim().to_string()))
+            },
+            None => (text, None)
+        };
+
+        let id = id.trim();
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_uppercase() || c == '_') {
+            return Err(format!("Condition subject {{{:}}} is not a valid variable name. ", id));
+        }
+
+        Ok(Self {
+            id: id.to_string(),
+            argument
+        })
+    }
+}
+
+fn split_value_list(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(|value| value.trim().to_string())
+        .collect()
+}
+
+#[test]
+fn condition_with_equals_parses() {
+    assert_eq!(Condition::from_string("PLATFORM == windows").unwrap(), Condition {
+        subject: ConditionSubject { id: "PLATFORM".to_string(), argument: None },
+        comparison: Comparison::Equals(vec!["windows".to_string()])
+    });
+}
+
+#[test]
+fn condition_with_not_equals_and_value_list_parses() {
+    assert_eq!(Condition::from_string(" LANGUAGE != en, fr ").unwrap(), Condition {
+        subject: ConditionSubject { id: "LANGUAGE".to_string(), argument: None },
+        comparison: Comparison::NotEquals(vec!["en".to_string(), "fr".to_string()])
+    });
+}
+
+#[test]
+fn condition_with_argument_parses() {
+    assert_eq!(Condition::from_string("!USER_VAR{TEAM}").unwrap(), Condition {
+        subject: ConditionSubject { id: "USER_VAR".to_string(), argument: Some("TEAM".to_string()) },
+        comparison: Comparison::IsNotSet
+    });
+}
+
+#[test]
+fn condition_with_bad_subject_fails() {
+    assert!(Condition::from_string("").is_err());
+    assert!(Condition::from_string("platform == windows").is_err());
+    assert!(Condition::from_string("USER_VAR{TEAM == core").is_err());
+}
+
+#[test]
+fn condition_evaluates_against_subject_value() {
+    let condition = Condition::from_string("PLATFORM == windows, linux").unwrap();
+    assert!(condition.evaluate(&Some("linux".to_string())));
+    assert!(!condition.evaluate(&Some("mac_os".to_string())));
+    assert!(!condition.evaluate(&None));
+
+    let condition = Condition::from_string("PLATFORM != windows").unwrap();
+    assert!(condition.evaluate(&None));
+    assert!(!condition.evaluate(&Some("windows".to_string())));
+
+    let condition = Condition::from_string("ENUMERATION").unwrap();
+    assert!(condition.evaluate(&Some("a".to_string())));
+    assert!(!condition.evaluate(&None));
+}
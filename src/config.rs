@@ -1,25 +1,51 @@
 use log::*;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{read_to_string, File};
 use std::io::Write;
 
 use serde::{Serialize, Deserialize};
 
+use crate::suggestion::did_you_mean;
+use crate::file_context::FileEnumeration;
+use crate::naming_pattern::NAMING_PATTERN_TOKENS;
+use crate::symbol_replacer::KNOWN_TOKENS;
+
+// The axes every configuration has, -p, -l and -e are short for --axis with these. 
+pub const PLATFORM_AXIS: &str = "platform";
+pub const LANGUAGE_AXIS: &str = "language";
+pub const ENUMERATION_AXIS: &str = "enumeration";
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
-	pub enumeration_list: Vec<String>,
-	pub language_list: Vec<String>,
-	pub platform_list: Vec<String>,
+	pub enumeration_list: Vec<EnumerationValue>,
+	pub language_list: Vec<EnumerationValue>,
+	pub platform_list: Vec<EnumerationValue>,
 	pub user_variables: HashMap<String, String>,
 
-	pub partner_file_map: HashMap<String, String>
+	pub partner_file_map: HashMap<String, String>,
+
+	// Any other lists files can be enumerated over, ie: a build variant or a board. 
+	#[serde(default)]
+	pub axes: Vec<EnumerationAxis>,
+
+	// Which axes are paired up instead of crossed, and which combinations of values never get a file. 
+	#[serde(default)]
+	pub enumeration_rules: EnumerationRules,
+
+	// How enumerated files are named, ie: "[]NAME[].[]PLATFORM[]". Templates can override it with NAMING_PATTERN. 
+	#[serde(default)]
+	pub naming_pattern: Option<String>,
+
+	// Same as passing --strict, for projects that never want a file with ERR in it. 
+	#[serde(default)]
+	pub strict: bool
 }
 
 impl Config {
 	pub fn load(path: &str) -> Result<Self, String> {
 		let raw_config = read_to_string(path);
-		let config: Self; 
+		let mut config: Self;
 		match raw_config {
 			Ok(val) => config = serde_json::from_str(&val).unwrap(),
 			Err(e) => {
@@ -27,9 +53,22 @@ impl Config {
 				return Err("Failed to load configuration file. ".to_string())}
 		}
 
+		config.remove_invalid_axes();
+
 		return Ok(config);
 	}
 
+	// Axes that can't be used are reported once when the configuration is loaded and left out from then on. 
+	pub fn remove_invalid_axes(&mut self) {
+		self.axes.retain(|axis| match axis.check_name() {
+			Ok(()) => true,
+			Err(e) => {
+				error!("Skipping enumeration axis {{{:}}}. {:}", axis.name, e);
+				false
+			}
+		});
+	}
+
 	pub fn write(self: &Self, path: &str) -> Result<(), String> {
 
 		let arg_dump = serde_json::to_string(&self);
@@ -53,13 +92,109 @@ impl Config {
 		Ok(())
 	} 
 
+	// Variables passed on the command line take precedence over the ones in the configuration file, 
+	//  and ones passed with --var take precedence over the ones from --vars-file. 
+	pub fn override_user_variables(&mut self, variables_file: &Option<String>, variables: &Vec<String>) -> Result<(), String> {
+		if let Some(variables_file) = variables_file {
+			for (name, value) in load_variables_file(variables_file)? {
+				self.user_variables.insert(name, value);
+			}
+		}
+
+		for variable in variables {
+			let (name, value) = parse_variable(variable)?;
+			self.user_variables.insert(name, value);
+		}
+
+		Ok(())
+	}
+
+	// The platform, language and enumeration lists are axes like any other, they just always come first. 
+	//  An axis in the axes list with one of their names replaces it. 
+	pub fn enumeration_axes(&self) -> Vec<EnumerationAxis> {
+		let mut axes = vec![
+			EnumerationAxis::with_values(PLATFORM_AXIS, &self.platform_list),
+			EnumerationAxis::with_values(LANGUAGE_AXIS, &self.language_list),
+			EnumerationAxis::with_values(ENUMERATION_AXIS, &self.enumeration_list)
+		];
+
+		for axis in &self.axes {
+			match axes.iter_mut().find(|existing_axis| existing_axis.name == axis.name) {
+				Some(existing_axis) => *existing_axis = axis.clone(),
+				None => axes.push(axis.clone())
+			}
+		}
+
+		axes
+	}
+
+	// The axis whose token is token_id, ie: BUILD_VARIANT for build_variant. 
+	pub fn enumeration_axis_for_token(&self, token_id: &str) -> Option<EnumerationAxis> {
+		self.enumeration_axes().into_iter().find(|axis| axis.token_id() == token_id)
+	}
+
+	// The axes that were asked for, always in the order they are in the configuration so
+	//  that file names come out the same no matter what order the flags were passed in. 
+	//  Selections are written as the axis name with an optional list of values, ie: platform=linux,mac_os
+	pub fn selected_enumeration_axes(&self, selections: &[String]) -> Result<Vec<EnumerationAxis>, String> {
+		let axes = self.enumeration_axes();
+
+		// The same axis can be picked more than once, ie: --platforms linux --axis platform=mac_os
+		let mut axis_selections: Vec<AxisSelection> = Vec::new();
+		for selection in selections {
+			let selection = AxisSelection::from_string(selection)?;
+
+			if !axes.iter().any(|axis| axis.name == selection.name) {
+				let axis_names: Vec<&String> = axes.iter().map(|axis| &axis.name).collect();
+				let suggestion = match did_you_mean(&selection.name, &axis_names) {
+					Some(suggestion) => format!(" Did you mean {:}?", suggestion),
+					None => String::new()
+				};
+				return Err(format!("There is no enumeration axis named {:} in the configuration file.{:}", selection.name, suggestion));
+			}
+
+			match axis_selections.iter_mut().find(|existing_selection| existing_selection.name == selection.name) {
+				Some(existing_selection) => existing_selection.merge(selection),
+				None => axis_selections.push(selection)
+			}
+		}
+
+		Ok(axes
+			.iter()
+			.filter_map(|axis| axis_selections.iter().find(|selection| selection.name == axis.name).map(|selection| selection.apply(axis)))
+			.collect())
+	}
+
+	// Adds an axis that only exists for this run, written as name=value,value. It replaces any axis with the same name. 
+	pub fn add_inline_enumeration_axis(&mut self, text: &str) -> Result<String, String> {
+		let axis = match text.split_once('=') {
+			Some((name, values)) => EnumerationAxis::new(name.trim(), &split_axis_values(values)),
+			None => return Err(format!("Inline enumerations are written as name=value,value, got {:}. ", text))
+		};
+
+		axis.check_name()?;
+		if axis.values.is_empty() {
+			return Err(format!("Inline enumeration {:} needs at least one value. ", axis.name));
+		}
+
+		let name = axis.name.clone();
+		self.axes.retain(|existing_axis| existing_axis.name != name);
+		self.axes.push(axis);
+
+		Ok(name)
+	}
+
 	pub fn default() -> Self {
 		let mut config = Config {
-			enumeration_list: vec!["a".to_string(), "b".to_string()],
-			language_list: vec!["en".to_string(), "fr".to_string()],
-			platform_list: vec!["windows".to_string(), "linux".to_string(), "mac_os".to_string()],
+			enumeration_list: vec!["a".into(), "b".into()],
+			language_list: vec!["en".into(), "fr".into()],
+			platform_list: vec!["windows".into(), "linux".into(), "mac_os".into()],
 			user_variables: HashMap::new(),
-			partner_file_map: HashMap::new()
+			partner_file_map: HashMap::new(),
+			axes: Vec::new(),
+			enumeration_rules: EnumerationRules::default(),
+			naming_pattern: None,
+			strict: false
 		};
 
 		config.user_variables.insert("LOOPBACK_ADDR".to_string(), "127.0.0.1".to_string());
@@ -72,3 +207,378 @@ impl Config {
 		config
 	}
 }
+
+// A list of values that files can be enumerated over. Each value gets its own file, and the
+//  axis' name in caps is the token that evaluates to the value, ie: []BOARD[] for board. 
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EnumerationAxis {
+	pub name: String,
+	pub values: Vec<EnumerationValue>
+}
+
+impl EnumerationAxis {
+	pub fn new(name: &str, values: &[String]) -> Self {
+		Self {
+			name: name.to_string(),
+			values: values.iter().map(|value| EnumerationValue::Name(value.clone())).collect()
+		}
+	}
+
+	pub fn with_values(name: &str, values: &[EnumerationValue]) -> Self {
+		Self {
+			name: name.to_string(),
+			values: values.to_vec()
+		}
+	}
+
+	pub fn value_names(&self) -> Vec<String> {
+		self.values.iter().map(|value| value.name().to_string()).collect()
+	}
+
+	pub fn find_value(&self, name: &str) -> Option<&EnumerationValue> {
+		self.values.iter().find(|value| value.name() == name)
+	}
+
+	pub fn token_id(&self) -> String {
+		self.name.to_uppercase()
+	}
+
+	// An axis' name is also a token name, so it can't be the name of a token tt already has. The built in
+	//  axes are the exception since an axis with one of their names replaces them. 
+	fn check_name(&self) -> Result<(), String> {
+		if self.name.is_empty() || !self.name.chars().all(|c| c.is_ascii_alphabetic() || c == '_') {
+			return Err(format!("Enumeration axis {{{:}}} can only have letters and underscores in its name since it's also a token name. ", self.name));
+		}
+
+		let token_id = self.token_id();
+		let is_built_in_axis = [PLATFORM_AXIS, LANGUAGE_AXIS, ENUMERATION_AXIS].iter().any(|axis| axis.to_uppercase() == token_id);
+		if !is_built_in_axis && (KNOWN_TOKENS.contains(&token_id.as_str()) || NAMING_PATTERN_TOKENS.contains(&token_id.as_str())) {
+			return Err(format!("Enumeration axis {{{:}}} has the same name as the {:} token, it needs a name of its own. ", self.name, token_id));
+		}
+
+		Ok(())
+	}
+}
+
+// A value of an enumeration axis. In the configuration file it's either just the value, ie: "linux", or an
+//  object with the value's name and a table of anything templates need to know about it, ie: 
+//  { "name": "linux", "export_macro": "LINUX_EXPORT" }. Templates read the table with []PLATFORM_VALUE{export_macro}[]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum EnumerationValue {
+	Name(String),
+	WithTable {
+		name: String,
+		#[serde(flatten)]
+		table: HashMap<String, serde_json::Value>
+	}
+}
+
+impl EnumerationValue {
+	pub fn name(&self) -> &str {
+		match self {
+			EnumerationValue::Name(name) | EnumerationValue::WithTable { name, .. } => name
+		}
+	}
+
+	pub fn table_value(&self, key: &str) -> Option<&serde_json::Value> {
+		match self {
+			EnumerationValue::Name(_) => None,
+			EnumerationValue::WithTable { table, .. } => table.get(key)
+		}
+	}
+
+	pub fn table_keys(&self) -> Vec<&String> {
+		match self {
+			EnumerationValue::Name(_) => Vec::new(),
+			EnumerationValue::WithTable { table, .. } => table.keys().collect()
+		}
+	}
+}
+
+impl From<&str> for EnumerationValue {
+	fn from(name: &str) -> Self {
+		EnumerationValue::Name(name.to_string())
+	}
+}
+
+// How the selected axes are combined. Axes in the same zip list are paired up value by value, ie: the first
+//  platform with the first toolchain, instead of there being a file for every combination of them. A file is
+//  left out when its values match everything in one of the exclusions, ie: { "board": "embedded", "language": "fr" }
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EnumerationRules {
+	#[serde(default)]
+	pub zip: Vec<Vec<String>>,
+
+	#[serde(default)]
+	pub exclude: Vec<BTreeMap<String, String>>
+}
+
+impl EnumerationRules {
+	// Rules can only be checked once we know which axes there are. 
+	pub fn check(&self, axes: &[EnumerationAxis]) -> Result<(), String> {
+		let axis_names: Vec<&String> = axes.iter().map(|axis| &axis.name).collect();
+		let check_axis_name = |axis_name: &String, rule: &str| -> Result<(), String> {
+			if axis_names.contains(&axis_name) {
+				return Ok(());
+			}
+			let suggestion = match did_you_mean(axis_name, &axis_names) {
+				Some(suggestion) => format!(" Did you mean {:}?", suggestion),
+				None => String::new()
+			};
+			Err(format!("There is no enumeration axis named {:} for the {:} in enumeration_rules.{:}", axis_name, rule, suggestion))
+		};
+
+		let mut zipped_axes: Vec<&String> = Vec::new();
+		for zipped_group in &self.zip {
+			for axis_name in zipped_group {
+				check_axis_name(axis_name, "zip")?;
+				if zipped_axes.contains(&axis_name) {
+					return Err(format!("{:} is in more than one zip list in enumeration_rules, an axis can only be paired up once. ", axis_name));
+				}
+				zipped_axes.push(axis_name);
+			}
+		}
+
+		for exclusion in &self.exclude {
+			if exclusion.is_empty() {
+				return Err("An exclusion in enumeration_rules is empty, it would leave out every file. ".to_string());
+			}
+			for axis_name in exclusion.keys() {
+				check_axis_name(axis_name, "exclusion")?;
+			}
+		}
+
+		Ok(())
+	}
+
+	// The first exclusion that leaves this file out, if any. An exclusion only matches files that were 
+	//  enumerated over every axis in it. 
+	pub fn excluded_by(&self, enumerations: &FileEnumeration) -> Option<&BTreeMap<String, String>> {
+		self.exclude.iter().find(|exclusion| exclusion
+			.iter()
+			.all(|(axis_name, value)| enumerations.get(axis_name).as_ref() == Some(value)))
+	}
+}
+
+// Which values of an axis to use, values starting with ! are left out. Without any values
+//  to use every value from the configuration is used, minus the ones left out. 
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct AxisSelection {
+	name: String,
+	values: Vec<String>,
+	excluded_values: Vec<String>
+}
+
+impl AxisSelection {
+	fn from_string(text: &str) -> Result<Self, String> {
+		let (name, values) = match text.split_once('=') {
+			Some((name, values)) => (name.trim(), split_axis_values(values)),
+			None => (text.trim(), Vec::new())
+		};
+
+		if name.is_empty() {
+			return Err(format!("Enumeration selection {:} has no axis name. ", text));
+		}
+
+		let (excluded_values, values): (Vec<String>, Vec<String>) = values.into_iter().partition(|value| value.starts_with('!'));
+
+		Ok(Self {
+			name: name.to_string(),
+			values,
+			excluded_values: excluded_values.iter().map(|value| value[1..].trim().to_string()).collect()
+		})
+	}
+
+	fn merge(&mut self, other: Self) {
+		for value in other.values {
+			if !self.values.contains(&value) {
+				self.values.push(value);
+			}
+		}
+		self.excluded_values.extend(other.excluded_values);
+	}
+
+	fn apply(&self, axis: &EnumerationAxis) -> EnumerationAxis {
+		let axis_values = axis.value_names();
+		for value in self.values.iter().filter(|value| !axis_values.contains(value)) {
+			info!("{:} isn't one of the {:} values in the configuration file, using it anyway. ", value, axis.name);
+		}
+		for value in self.excluded_values.iter().filter(|value| !axis_values.contains(value) && !self.values.contains(value)) {
+			match did_you_mean(value, &axis_values) {
+				Some(suggestion) => warn!("Can't leave out {:}, it isn't one of the {:} values. Did you mean {:}?", value, axis.name, suggestion),
+				None => warn!("Can't leave out {:}, it isn't one of the {:} values. ", value, axis.name)
+			}
+		}
+
+		// Values picked on the command line keep their table if the configuration has one for them. 
+		let values: Vec<EnumerationValue> = if self.values.is_empty() { 
+			axis.values.clone() 
+		} else { 
+			self.values.iter().map(|value| axis.find_value(value).cloned().unwrap_or(EnumerationValue::Name(value.clone()))).collect() 
+		};
+		let values: Vec<EnumerationValue> = values.into_iter().filter(|value| !self.excluded_values.iter().any(|excluded_value| excluded_value == value.name())).collect();
+		if values.is_empty() {
+			warn!("Every value of {:} was left out, files won't be enumerated over it. ", axis.name);
+		}
+
+		EnumerationAxis::with_values(&axis.name, &values)
+	}
+}
+
+fn split_axis_values(values: &str) -> Vec<String> {
+	values.split(',').map(|value| value.trim().to_string()).filter(|value| !value.is_empty()).collect()
+}
+
+// Variables from the command line are written as NAME=VALUE. 
+pub fn parse_variable(text: &str) -> Result<(String, String), String> {
+	match text.split_once('=') {
+		Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.to_string())),
+		_ => Err(format!("Variables are written as NAME=VALUE, got {:}. ", text))
+	}
+}
+
+// A variables file is a JSON object, values can be strings, numbers or booleans. 
+pub fn load_variables_file(path: &str) -> Result<HashMap<String, String>, String> {
+	let raw_variables = read_to_string(path).map_err(|e| format!("Failed to load variables file {:}. {:}", path, e))?;
+	let variables: HashMap<String, serde_json::Value> = serde_json::from_str(&raw_variables)
+		.map_err(|e| format!("Variables file {:} should be a JSON object of variable name to value. {:}", path, e))?;
+
+	let mut user_variables: HashMap<String, String> = HashMap::new();
+	for (name, value) in variables {
+		let value = match value {
+			serde_json::Value::String(text) => text,
+			serde_json::Value::Number(number) => number.to_string(),
+			serde_json::Value::Bool(boolean) => boolean.to_string(),
+			_ => return Err(format!("Variable {:} in {:} needs to be a string, number or boolean. ", name, path))
+		};
+		user_variables.insert(name, value);
+	}
+
+	Ok(user_variables)
+}
+
+#[test]
+fn command_line_variables_override_config() {
+	let variables_file = std::env::temp_dir().join(format!("tt_vars_file_test_{:}.json", std::process::id()));
+	std::fs::write(&variables_file, r#"{ "TEAM": "tools", "COUNT": 3, "VERSION_MANAGEMENT": "svn" }"#).unwrap();
+
+	let mut config = Config::default();
+	config.override_user_variables(
+		&Some(variables_file.to_str().unwrap().to_string()), 
+		&vec!["TEAM=core=tools".to_string()]).unwrap();
+
+	assert_eq!("core=tools", config.user_variables["TEAM"]);
+	assert_eq!("3", config.user_variables["COUNT"]);
+	assert_eq!("svn", config.user_variables["VERSION_MANAGEMENT"]);
+	assert_eq!("127.0.0.1", config.user_variables["LOOPBACK_ADDR"]);
+
+	assert!(config.override_user_variables(&None, &vec!["TEAM".to_string()]).is_err());
+
+	let _ = std::fs::remove_file(&variables_file);
+}
+
+#[test]
+fn axes_from_config_come_after_the_built_in_ones() {
+	let mut config = Config::default();
+	config.axes.push(EnumerationAxis::new("board", &["stm32".to_string(), "esp32".to_string()]));
+	config.axes.push(EnumerationAxis::new("language", &["de".to_string()]));
+	config.axes.push(EnumerationAxis::new("api_v2", &["1".to_string()]));
+	config.remove_invalid_axes();
+	assert_eq!(2, config.axes.len());
+
+	let axis_names: Vec<String> = config.enumeration_axes().into_iter().map(|axis| axis.name).collect();
+	assert_eq!(vec!["platform", "language", "enumeration", "board"], axis_names);
+	assert_eq!(Some(vec!["de".to_string()]), config.enumeration_axis_for_token("LANGUAGE").map(|axis| axis.value_names()));
+
+	let selected_axes = config.selected_enumeration_axes(&["board".to_string(), "platform".to_string()]).unwrap();
+	assert_eq!(vec!["platform", "board"], selected_axes.iter().map(|axis| axis.name.as_str()).collect::<Vec<&str>>());
+
+	assert!(config.selected_enumeration_axes(&["bord".to_string()]).unwrap_err().ends_with("Did you mean board?"));
+}
+
+#[test]
+fn axes_cannot_share_a_name_with_a_token() {
+	let mut config = Config::default();
+	for name in ["user", "OS", "name", "version", "enumerations", "file_name"] {
+		config.axes.push(EnumerationAxis::new(name, &["a".to_string()]));
+		assert_eq!(
+			Err(format!("Enumeration axis {{{:}}} has the same name as the {:} token, it needs a name of its own. ", name, name.to_uppercase())),
+			config.add_inline_enumeration_axis(&(name.to_string() + "=a")));
+	}
+
+	config.remove_invalid_axes();
+	let axis_names: Vec<String> = config.enumeration_axes().into_iter().map(|axis| axis.name).collect();
+	assert_eq!(vec!["platform", "language", "enumeration"], axis_names);
+}
+
+#[test]
+fn axis_values_can_be_picked_and_left_out() {
+	let mut config = Config::default();
+	config.axes.push(EnumerationAxis::new("board", &["stm32".to_string(), "esp32".to_string(), "rp2040".to_string()]));
+
+	let selected_axes = config.selected_enumeration_axes(&[
+		"board=!esp32".to_string(),
+		"platform=linux, mac_os".to_string(),
+		"platform=freebsd".to_string()]).unwrap();
+
+	assert_eq!(vec![
+		EnumerationAxis::new("platform", &["linux".to_string(), "mac_os".to_string(), "freebsd".to_string()]),
+		EnumerationAxis::new("board", &["stm32".to_string(), "rp2040".to_string()])], selected_axes);
+}
+
+#[test]
+fn inline_axes_replace_ones_from_config() {
+	let mut config = Config::default();
+	config.axes.push(EnumerationAxis::new("board", &["stm32".to_string()]));
+
+	assert_eq!(Ok("board".to_string()), config.add_inline_enumeration_axis("board=a, b,c"));
+	assert_eq!(vec![EnumerationAxis::new("board", &["a".to_string(), "b".to_string(), "c".to_string()])], config.selected_enumeration_axes(&["board".to_string()]).unwrap());
+
+	assert!(config.add_inline_enumeration_axis("board").is_err());
+	assert!(config.add_inline_enumeration_axis("board=").is_err());
+	assert!(config.add_inline_enumeration_axis("api v2=1").is_err());
+	assert!(config.add_inline_enumeration_axis("platform=linux").is_ok());
+}
+
+#[test]
+fn enumeration_values_can_have_tables() {
+	let config: Config = serde_json::from_str(r#"{
+		"enumeration_list": [ "a" ],
+		"language_list": [ "en", { "name": "fr", "display_name": "Français" } ],
+		"platform_list": [ { "name": "linux", "export_macro": "LINUX_EXPORT", "bits": 64 } ],
+		"user_variables": {},
+		"partner_file_map": {},
+		"axes": [ { "name": "board", "values": [ { "name": "stm32", "flash_kb": 512 }, "esp32" ] } ]
+	}"#).unwrap();
+
+	let language = config.enumeration_axis_for_token("LANGUAGE").unwrap();
+	assert_eq!(vec!["en", "fr"], language.value_names());
+	assert_eq!(None, language.find_value("en").unwrap().table_value("display_name"));
+	assert_eq!(Some(&serde_json::Value::from("Français")), language.find_value("fr").unwrap().table_value("display_name"));
+
+	let selected_axes = config.selected_enumeration_axes(&["platform=linux,freebsd".to_string(), "board=!esp32".to_string()]).unwrap();
+	assert_eq!(Some(&serde_json::Value::from(64)), selected_axes[0].find_value("linux").unwrap().table_value("bits"));
+	assert_eq!(Some(&EnumerationValue::from("freebsd")), selected_axes[0].find_value("freebsd"));
+	assert_eq!(Some(&serde_json::Value::from(512)), selected_axes[1].find_value("stm32").unwrap().table_value("flash_kb"));
+}
+
+#[test]
+fn enumeration_rules_are_checked_and_exclude_files() {
+	let mut config = Config::default();
+	config.axes.push(EnumerationAxis::new("toolchain", &["msvc".to_string(), "gcc".to_string()]));
+	config.enumeration_rules = serde_json::from_str(r#"{ "zip": [ [ "platform", "toolchain" ] ], "exclude": [ { "platform": "linux", "language": "fr" } ] }"#).unwrap();
+	assert_eq!(Ok(()), config.enumeration_rules.check(&config.enumeration_axes()));
+
+	let mut enumerations = FileEnumeration::blank();
+	enumerations.set("platform", "linux");
+	assert_eq!(None, config.enumeration_rules.excluded_by(&enumerations));
+	enumerations.set("language", "fr");
+	assert_eq!(Some(&config.enumeration_rules.exclude[0]), config.enumeration_rules.excluded_by(&enumerations));
+
+	config.enumeration_rules.zip.push(vec!["toolchian".to_string()]);
+	assert!(config.enumeration_rules.check(&config.enumeration_axes()).unwrap_err().ends_with("Did you mean toolchain?"));
+
+	config.enumeration_rules.zip = vec![vec!["platform".to_string()], vec!["platform".to_string(), "language".to_string()]];
+	assert!(config.enumeration_rules.check(&config.enumeration_axes()).is_err());
+}
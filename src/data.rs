@@ -0,0 +1,87 @@
+use std::fs::read_to_string;
+
+use serde_json::Value;
+
+use crate::diagnostic::TokenError;
+use crate::suggestion::did_you_mean;
+
+// The file passed with --data. DATA reads values out of it by path, ie: fields.0.name, and
+//  FOR_EACH_ITEM loops over the arrays in it.
+pub fn load_data_file(path: &str) -> Result<Value, String> {
+    let raw_data = read_to_string(path).map_err(|e| format!("Failed to load data file {:}. {:}", path, e))?;
+
+    serde_json::from_str(&raw_data).map_err(|e| format!("Data file {:} is not valid JSON. {:}", path, e))
+}
+
+// Follows a path of object keys and array indices separated by dots. An empty path is the value itself.
+pub fn find_value<'v>(value: &'v Value, path: &str) -> Result<&'v Value, TokenError> {
+    let mut current_value = value;
+    let mut current_path: Vec<&str> = Vec::new();
+
+    for key in path.split('.').map(|key| key.trim()).filter(|key| !key.is_empty()) {
+        current_value = match current_value {
+            Value::Object(object) => match object.get(key) {
+                Some(next_value) => next_value,
+                None => {
+                    let keys: Vec<&String> = object.keys().collect();
+                    return Err(TokenError::with_argument(&format!("{:} has no field named {:}. ", describe_path(&current_path), key), 0)
+                        .with_suggestion(did_you_mean(key, &keys)));
+                }
+            },
+            Value::Array(array) => match key.parse::<usize>() {
+                Ok(index) if index < array.len() => &array[index],
+                Ok(index) => return Err(TokenError::with_argument(&format!("{:} only has {:} item(s), there is no item {:}. ", describe_path(&current_path), array.len(), index), 0)),
+                Err(_) => return Err(TokenError::with_argument(&format!("{:} is an array, {:} needs to be an index. ", describe_path(&current_path), key), 0))
+            },
+            _ => return Err(TokenError::with_argument(&format!("{:} is a single value, it has no field named {:}. ", describe_path(&current_path), key), 0))
+        };
+
+        current_path.push(key);
+    }
+
+    Ok(current_value)
+}
+
+// Strings are placed as is, anything else is placed as JSON.
+pub fn value_as_string(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        _ => value.to_string()
+    }
+}
+
+// Null and false count as not being set when used in an IF condition.
+pub fn value_as_condition(value: &Value) -> Option<String> {
+    match value {
+        Value::Null | Value::Bool(false) => None,
+        _ => Some(value_as_string(value))
+    }
+}
+
+fn describe_path(path: &[&str]) -> String {
+    if path.is_empty() { "The data".to_string() } else { path.join(".") }
+}
+
+#[test]
+fn values_are_found_by_path() {
+    let data: Value = serde_json::from_str(r#"{ "name": "Telemetry", "fields": [ { "name": "speed", "size": 4 } ] }"#).unwrap();
+
+    assert_eq!("Telemetry", value_as_string(find_value(&data, "name").unwrap()));
+    assert_eq!("speed", value_as_string(find_value(&data, "fields.0.name").unwrap()));
+    assert_eq!("4", value_as_string(find_value(&data, "fields.0.size").unwrap()));
+    assert_eq!(data, *find_value(&data, "").unwrap());
+}
+
+#[test]
+fn bad_paths_say_what_went_wrong() {
+    let data: Value = serde_json::from_str(r#"{ "fields": [ { "name": "speed" } ] }"#).unwrap();
+
+    let error = find_value(&data, "fields.0.nmae").unwrap_err();
+    assert_eq!("fields.0 has no field named nmae. ", error.message);
+    assert_eq!(Some("name".to_string()), error.suggestion);
+
+    assert!(find_value(&data, "fields.1").is_err());
+    assert!(find_value(&data, "fields.first").is_err());
+    assert!(find_value(&data, "fields.0.name.length").is_err());
+}
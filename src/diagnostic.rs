@@ -0,0 +1,164 @@
+use std::fmt;
+
+use crate::parser::{Position, Span};
+
+// What a token gives back when it can't be evaluated. If one of the token's parameters
+//  is to blame, argument is the index of that parameter so that it can be pointed at.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TokenError {
+    pub message: String,
+    pub argument: Option<usize>,
+    pub suggestion: Option<String>
+}
+
+impl TokenError {
+    pub fn new(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+            argument: None,
+            suggestion: None
+        }
+    }
+
+    pub fn with_argument(message: &str, argument: usize) -> Self {
+        Self {
+            message: message.to_string(),
+            argument: Some(argument),
+            suggestion: None
+        }
+    }
+
+    // What the user most likely meant to write, shown as a "Did you mean?" under the error.
+    pub fn with_suggestion(mut self, suggestion: Option<String>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+}
+
+// A problem found while evaluating a template, along with everything needed to show the
+//  user where it is.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub template_path: String,
+    pub position: Position,
+    pub source_line: String,
+    pub highlight_length: usize,
+
+    pub token: String,
+    pub argument: Option<String>,
+    pub message: String,
+    pub suggestion: Option<String>
+}
+
+impl Diagnostic {
+    pub fn new(
+        template_path: &str,
+        template_text: &str,
+        span: Span,
+        token: &str,
+        argument: Option<String>,
+        message: &str) -> Self {
+
+        let source_line = template_text.lines().nth(span.start.line - 1).unwrap_or("").to_string();
+
+        // Anything that goes past the end of the line just gets highlighted to the end of the line.
+        let characters_left_in_line = source_line.chars().count().saturating_sub(span.start.column - 1);
+        let highlight_length =
+            if span.end.line == span.start.line { span.end.column.saturating_sub(span.start.column) }
+            else                                { characters_left_in_line };
+
+        Self {
+            template_path: template_path.to_string(),
+            position: span.start,
+            source_line,
+            highlight_length: highlight_length.clamp(1, characters_left_in_line.max(1)),
+
+            token: token.to_string(),
+            argument,
+            message: message.to_string(),
+            suggestion: None
+        }
+    }
+
+    pub fn with_suggestion(mut self, suggestion: Option<String>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.argument {
+            Some(argument) => writeln!(f, "{:} {:}: {:}", self.token, argument, self.message.trim_end())?,
+            None           => writeln!(f, "{:}: {:}", self.token, self.message.trim_end())?
+        }
+
+        let line_number = self.position.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        // Tabs are kept in front of the caret so that it lines up with the source line.
+        let caret_indent: String = self.source_line
+            .chars()
+            .take(self.position.column - 1)
+            .map(|character| if character == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(f, "{:}--> {:}:{:}:{:}", gutter, self.template_path, self.position.line, self.position.column)?;
+        writeln!(f, "{:} |", gutter)?;
+        writeln!(f, "{:} | {:}", line_number, self.source_line)?;
+        write!(f, "{:} | {:}{:}", gutter, caret_indent, "^".repeat(self.highlight_length))?;
+
+        match &self.suggestion {
+            Some(suggestion) => write!(f, "\n{:} = Did you mean {:}?", gutter, suggestion),
+            None => Ok(())
+        }
+    }
+}
+
+#[test]
+fn diagnostic_points_at_token() {
+    let template = "#pragma once\n\tclass []USER_VAR{TEAM}[] {};";
+    let span = Span {
+        start: Position { line: 2, column: 8 },
+        end: Position { line: 2, column: 26 }
+    };
+
+    let diagnostic = Diagnostic::new("template.h", template, span, "USER_VAR", Some("argument 1 {TEAM}".to_string()), "No user variable named TEAM exists. ");
+
+    assert_eq!(format!("{:}", diagnostic),
+"USER_VAR argument 1 {TEAM}: No user variable named TEAM exists.
+ --> template.h:2:8
+  |
+2 | \tclass []USER_VAR{TEAM}[] {};
+  | \t      ^^^^^^^^^^^^^^^^^^");
+}
+
+#[test]
+fn diagnostic_spanning_lines_highlights_to_end_of_line() {
+    let template = "[]IF{PLATFORM|||\nwindows}[]";
+    let span = Span {
+        start: Position { line: 1, column: 1 },
+        end: Position { line: 2, column: 11 }
+    };
+
+    let diagnostic = Diagnostic::new("template.h", template, span, "IF", None, "Bad condition. ");
+
+    assert_eq!(16, diagnostic.highlight_length);
+}
+
+#[test]
+fn diagnostic_shows_suggestion() {
+    let template = "[]FILE_NAME{kebab}[]";
+    let span = Span {
+        start: Position { line: 1, column: 13 },
+        end: Position { line: 1, column: 18 }
+    };
+
+    let diagnostic = Diagnostic::new("template.h", template, span, "FILE_NAME", Some("argument 1 {kebab}".to_string()), "No recognized formatting method for {kebab}. ")
+        .with_suggestion(Some("kabob".to_string()));
+
+    assert!(format!("{:}", diagnostic).ends_with(
+"1 | []FILE_NAME{kebab}[]
+  |             ^^^^^
+  = Did you mean kabob?"));
+}
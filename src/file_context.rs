@@ -1,5 +1,10 @@
+use std::collections::HashMap;
+
 use log::*;
 
+use crate::config::EnumerationAxis;
+use crate::naming_pattern::NamingPattern;
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct FileContext {
     pub name: String,
@@ -7,6 +12,12 @@ pub struct FileContext {
     pub path: String,
 
     pub enumerations: FileEnumeration,
+
+    // Template variables that only this file has, ie: the columns of its row when using --table. 
+    pub variables: HashMap<String, String>,
+
+    // How the file's name is put together from its name and enumerations. 
+    pub naming_pattern: NamingPattern,
 }
 
 impl FileContext {
@@ -16,7 +27,9 @@ impl FileContext {
             extension: String::new(),
             path: String::new(),
 
-            enumerations: FileEnumeration::blank()
+            enumerations: FileEnumeration::blank(),
+            variables: HashMap::new(),
+            naming_pattern: NamingPattern::default()
         }
     }
 
@@ -43,266 +56,249 @@ impl FileContext {
             path: path.to_string(),
             name: file_name,
 
-            enumerations: FileEnumeration::blank()
+            enumerations: FileEnumeration::blank(),
+            variables: HashMap::new(),
+            naming_pattern: NamingPattern::default()
         })
     }
 
     pub fn name_with_extension(self: &Self) -> String {
         if self.extension.is_empty() {
-            return self.expand_with_enumerations().to_string()
+            return self.file_stem()
         }
-        String::from(self.expand_with_enumerations()) + "." + &self.extension
+        self.file_stem() + "." + &self.extension
     }
 
-    pub fn expand_with_enumerations(self: &Self) -> String {
-         let mut platform_append: String = String::from("");
-        if self.enumerations.platform.is_some() {
-            platform_append = String::from("_") + &(self.enumerations.platform.as_ref().unwrap());
+    // Where the file gets written, relative to the current directory. The naming pattern can place it in a directory. 
+    pub fn output_path(&self) -> String {
+        match self.directory() {
+            Some(directory) => directory + "/" + &self.name_with_extension(),
+            None => self.name_with_extension()
         }
+    }
 
-        let mut language_append: String = String::from("");
-        if self.enumerations.language.is_some() {
-            language_append = String::from("_") + &(self.enumerations.language.as_ref().unwrap());
+    // The name of the file without its extension, built with the naming pattern. 
+    pub fn file_stem(&self) -> String {
+        let expanded_name = self.naming_pattern.expand(&self.name, &self.enumerations);
+        match expanded_name.rsplit_once('/') {
+            Some((_, file_stem)) => file_stem.to_string(),
+            None => expanded_name
         }
+    }
 
-        let mut enumeration_append: String = String::from("");
-        if self.enumerations.user_defined.is_some() {
-            enumeration_append = String::from("_") + &(self.enumerations.user_defined.as_ref().unwrap());
+    pub fn directory(&self) -> Option<String> {
+        let expanded_name = self.naming_pattern.expand(&self.name, &self.enumerations);
+        expanded_name.rsplit_once('/').map(|(directory, _)| directory.to_string())
+    }
+
+    // The name with every enumeration value appended with an _, this is what type names are made from. 
+    pub fn expand_with_enumerations(self: &Self) -> String {
+        let mut expanded_name = self.name.clone();
+        for value in self.enumerations.values() {
+            expanded_name += &(String::from("_") + value);
         }
 
-        String::from(&self.name) + &platform_append + &language_append + &enumeration_append
+        expanded_name
     }
 
-    pub fn enumerate(
-        self: &Self, 
-        platform_list: &Vec<String>, 
-        language_list: &Vec<String>, 
-        user_enumeration_list: &Vec<String>) -> Vec<Self> {
+    // Every combination of the axes' values gets its own file, except for axes in the same zipped list. 
+    //  Those are paired up value by value, ie: the first platform only goes with the first toolchain. 
+    pub fn enumerate(&self, axes: &[EnumerationAxis], zipped_axes: &[Vec<String>]) -> Vec<Self> {
 
         let mut expanded_list: Vec<Self> = Vec::new();
         expanded_list.push(self.clone());
 
-        let mut files_with_platform: Vec<Self> = Vec::new();
-        for platform in platform_list {
-            let mut new_context = self.clone();
-
-            new_context.enumerations.platform = Some(platform.clone());
-
-            files_with_platform.push(new_context);
-        }
-
-        if !files_with_platform.is_empty() {
-            expanded_list = files_with_platform;
-        }
-
-        let mut files_with_language: Vec<Self> = Vec::new();
-        for file in &expanded_list {
-            for language in language_list {
-                let mut new_context = file.clone();
-
-                new_context.enumerations.language = Some(language.clone());
+        let mut enumerated_axes: Vec<&String> = Vec::new();
+        for axis in axes {
+            if enumerated_axes.contains(&&axis.name) {
+                continue;
+            }
 
-                files_with_language.push(new_context);
+            let paired_axes: Vec<&EnumerationAxis> = match zipped_axes.iter().find(|zipped_group| zipped_group.contains(&axis.name)) {
+                Some(zipped_group) => axes.iter().filter(|paired_axis| zipped_group.contains(&paired_axis.name) && !paired_axis.values.is_empty()).collect(),
+                None => vec![axis]
+            };
+            enumerated_axes.push(&axis.name);
+            enumerated_axes.extend(paired_axes.iter().map(|paired_axis| &paired_axis.name));
+
+            let row_count = paired_axes.iter().map(|paired_axis| paired_axis.values.len()).min().unwrap_or(0);
+            if paired_axes.iter().any(|paired_axis| paired_axis.values.len() != row_count) {
+                let axis_names: Vec<&str> = paired_axes.iter().map(|paired_axis| paired_axis.name.as_str()).collect();
+                warn!("{:} are paired up but don't have the same number of values, only the first {:} of each are used. ", axis_names.join(", "), row_count);
             }
-        }
 
-        if !files_with_language.is_empty() {
-            expanded_list = files_with_language;
-        }
+            if row_count == 0 {
+                continue;
+            }
 
-        let mut files_with_enumeration: Vec<Self> = Vec::new();
-        for file in &expanded_list {
-            for user_enumeration in user_enumeration_list {
-                let mut new_context = file.clone();
+            let mut files_with_axis: Vec<Self> = Vec::new();
+            for file in &expanded_list {
+                for row in 0..row_count {
+                    let mut new_context = file.clone();
 
-                new_context.enumerations.user_defined = Some(user_enumeration.clone());
+                    for paired_axis in &paired_axes {
+                        new_context.enumerations.set(&paired_axis.name, paired_axis.values[row].name());
+                    }
 
-                files_with_enumeration.push(new_context);
+                    files_with_axis.push(new_context);
+                }
             }
-        }
 
-        if !files_with_enumeration.is_empty() {
-            expanded_list = files_with_enumeration;
+            expanded_list = files_with_axis;
         }
 
         expanded_list
     }
 }
 
+// The value this file has for each axis it was enumerated over, ie: platform is linux. 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct FileEnumeration {
-    pub language: Option<String>,
-    pub platform: Option<String>,
-    pub user_defined: Option<String>
+    axis_values: Vec<(String, String)>
 }
 
 impl FileEnumeration {
     pub fn blank() -> Self {
         Self {
-            language: None,
-            platform: None,
-            user_defined: None
+            axis_values: Vec::new()
         }
     }
-}
 
-#[test]
-pub fn enumerate_expands_with_platform() {
-    let mut expected_result: Vec<FileContext> = vec![FileContext::blank(), FileContext::blank()];
+    pub fn get(&self, axis: &str) -> Option<String> {
+        self.axis_values.iter().find(|(axis_name, _)| axis_name == axis).map(|(_, value)| value.clone())
+    }
+
+    pub fn set(&mut self, axis: &str, value: &str) {
+        match self.axis_values.iter_mut().find(|(axis_name, _)| axis_name == axis) {
+            Some((_, existing_value)) => *existing_value = value.to_string(),
+            None => self.axis_values.push((axis.to_string(), value.to_string()))
+        }
+    }
 
-    expected_result[0].enumerations = FileEnumeration {
-            platform: Some("windows".to_string()),
-            language: None,
-            user_defined: None
-    };
+    // Values in the order the file was enumerated with them. 
+    pub fn values(&self) -> impl Iterator<Item = &String> {
+        self.axis_values.iter().map(|(_, value)| value)
+    }
 
-    expected_result[1].enumerations = FileEnumeration {
-            platform: Some("linux".to_string()),
-            language: None,
-            user_defined: None
-    };
+    pub fn axis_values(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.axis_values.iter().map(|(axis, value)| (axis, value))
+    }
+}
 
-    let platform_list: Vec<String> = vec!["windows".to_string(), "linux".to_string()];
+#[cfg(test)]
+fn enumerated_file(axis_values: &[(&str, &str)]) -> FileContext {
+    let mut file_context = FileContext::blank();
+    for (axis, value) in axis_values {
+        file_context.enumerations.set(axis, value);
+    }
+    file_context
+}
 
-    assert_eq!(expected_result, FileContext::blank().enumerate(&platform_list, &Vec::new(), &Vec::new()));
+#[cfg(test)]
+fn test_axis(name: &str, values: &[&str]) -> EnumerationAxis {
+    EnumerationAxis::new(name, &values.iter().map(|value| value.to_string()).collect::<Vec<String>>())
 }
 
 #[test]
-pub fn enumerate_expands_with_language() {
-    let mut expected_result: Vec<FileContext> = vec![FileContext::blank(), FileContext::blank()];
-
-    expected_result[0].enumerations = FileEnumeration {
-            platform: None,
-            language: Some("en".to_string()),
-            user_defined: None
-    };
+pub fn enumerate_expands_with_platform() {
+    let expected_result: Vec<FileContext> = vec![
+        enumerated_file(&[("platform", "windows")]),
+        enumerated_file(&[("platform", "linux")])
+    ];
 
-    expected_result[1].enumerations = FileEnumeration {
-            platform: None,
-            language: Some("fr".to_string()),
-            user_defined: None
-    };
+    assert_eq!(expected_result, FileContext::blank().enumerate(&[test_axis("platform", &["windows", "linux"])], &[]));
+}
 
-    let language_list: Vec<String> = vec!["en".to_string(), "fr".to_string()];
+#[test]
+pub fn enumerate_expands_with_language() {
+    let expected_result: Vec<FileContext> = vec![
+        enumerated_file(&[("language", "en")]),
+        enumerated_file(&[("language", "fr")])
+    ];
 
-    assert_eq!(expected_result, FileContext::blank().enumerate(&Vec::new(), &language_list, &Vec::new()));
+    assert_eq!(expected_result, FileContext::blank().enumerate(&[test_axis("language", &["en", "fr"])], &[]));
 }
 
 #[test]
 pub fn enumerate_expands_with_user_defines() {
-    let mut expected_result: Vec<FileContext> = vec![FileContext::blank(), FileContext::blank()];
+    let expected_result: Vec<FileContext> = vec![
+        enumerated_file(&[("enumeration", "a")]),
+        enumerated_file(&[("enumeration", "b")])
+    ];
+
+    assert_eq!(expected_result, FileContext::blank().enumerate(&[test_axis("enumeration", &["a", "b"])], &[]));
+}
 
-    expected_result[0].enumerations = FileEnumeration {
-            platform: None,
-            language: None,
-            user_defined: Some("a".to_string())
-    };
+#[test]
+pub fn enumerate_expands_with_platform_and_language() {
+    let expected_result: Vec<FileContext> = vec![
+        enumerated_file(&[("platform", "windows"), ("language", "en")]),
+        enumerated_file(&[("platform", "windows"), ("language", "fr")]),
+        enumerated_file(&[("platform", "linux"), ("language", "en")]),
+        enumerated_file(&[("platform", "linux"), ("language", "fr")])
+    ];
 
-    expected_result[1].enumerations = FileEnumeration {
-            platform: None,
-            language: None,
-            user_defined: Some("b".to_string())
-    };
+    let axes = [test_axis("platform", &["windows", "linux"]), test_axis("language", &["en", "fr"])];
 
-    let user_list: Vec<String> = vec!["a".to_string(), "b".to_string()];
+    assert_eq!(expected_result, FileContext::blank().enumerate(&axes, &[]));
+}
 
-    assert_eq!(expected_result, FileContext::blank().enumerate(&Vec::new(), &Vec::new(), &user_list));
+#[test]
+pub fn enumerate_expands_with_platform_and_language_and_user_defined() {
+    let expected_result: Vec<FileContext> = vec![
+        enumerated_file(&[("platform", "windows"), ("language", "en"), ("enumeration", "a")]),
+        enumerated_file(&[("platform", "windows"), ("language", "en"), ("enumeration", "b")]),
+        enumerated_file(&[("platform", "windows"), ("language", "fr"), ("enumeration", "a")]),
+        enumerated_file(&[("platform", "windows"), ("language", "fr"), ("enumeration", "b")]),
+        enumerated_file(&[("platform", "linux"), ("language", "en"), ("enumeration", "a")]),
+        enumerated_file(&[("platform", "linux"), ("language", "en"), ("enumeration", "b")]),
+        enumerated_file(&[("platform", "linux"), ("language", "fr"), ("enumeration", "a")]),
+        enumerated_file(&[("platform", "linux"), ("language", "fr"), ("enumeration", "b")])
+    ];
+
+    let axes = [test_axis("platform", &["windows", "linux"]), test_axis("language", &["en", "fr"]), test_axis("enumeration", &["a", "b"])];
+
+    assert_eq!(expected_result, FileContext::blank().enumerate(&axes, &[]));
 }
 
 #[test]
-pub fn enumerate_expands_with_platform_and_language() {
-    let mut expected_result: Vec<FileContext> = Vec::new();
-    for _ in 0..4 {
-        expected_result.push(FileContext::blank());
-    }
+pub fn enumerate_expands_with_any_axis_and_names_files_in_order() {
+    let mut file_context = FileContext::blank();
+    file_context.name = "driver".to_string();
+    file_context.extension = "c".to_string();
 
-    expected_result[0].enumerations = FileEnumeration {
-            platform: Some("windows".to_string()),
-            language: Some("en".to_string()),
-            user_defined: None
-    };
-
-    expected_result[1].enumerations = FileEnumeration {
-            platform: Some("windows".to_string()),
-            language: Some("fr".to_string()),
-            user_defined: None
-    };
-
-    expected_result[2].enumerations = FileEnumeration {
-            platform: Some("linux".to_string()),
-            language: Some("en".to_string()),
-            user_defined: None
-    };
-
-    expected_result[3].enumerations = FileEnumeration {
-            platform: Some("linux".to_string()),
-            language: Some("fr".to_string()),
-            user_defined: None
-    };
-
-    let language_list: Vec<String> = vec!["en".to_string(), "fr".to_string()];
-    let platform_list: Vec<String> = vec!["windows".to_string(), "linux".to_string()];
-
-    assert_eq!(expected_result, FileContext::blank().enumerate(&platform_list, &language_list, &Vec::new()));
+    let axes = [test_axis("board", &["stm32", "esp32"]), test_axis("variant", &["debug"]), test_axis("api_version", &[])];
+    let file_names: Vec<String> = file_context.enumerate(&axes, &[]).iter().map(|file| file.name_with_extension()).collect();
+
+    assert_eq!(vec!["driver_stm32_debug.c", "driver_esp32_debug.c"], file_names);
 }
 
 #[test]
-pub fn enumerate_expands_with_platform_and_language_and_user_defined() {
-    let mut expected_result: Vec<FileContext> = Vec::new();
-    for _ in 0..8 {
-        expected_result.push(FileContext::blank());
-    }
-    expected_result[0].enumerations = FileEnumeration {
-            platform: Some("windows".to_string()),
-            language: Some("en".to_string()),
-            user_defined: Some("a".to_string())
-    };
-
-    expected_result[1].enumerations = FileEnumeration {
-            platform: Some("windows".to_string()),
-            language: Some("en".to_string()),
-            user_defined: Some("b".to_string())
-    };
-
-    expected_result[2].enumerations = FileEnumeration {
-            platform: Some("windows".to_string()),
-            language: Some("fr".to_string()),
-            user_defined: Some("a".to_string())
-    };
-
-    expected_result[3].enumerations = FileEnumeration {
-            platform: Some("windows".to_string()),
-            language: Some("fr".to_string()),
-            user_defined: Some("b".to_string())
-    };
-
-    expected_result[4].enumerations = FileEnumeration {
-            platform: Some("linux".to_string()),
-            language: Some("en".to_string()),
-            user_defined: Some("a".to_string())
-    };
-
-    expected_result[5].enumerations = FileEnumeration {
-            platform: Some("linux".to_string()),
-            language: Some("en".to_string()),
-            user_defined: Some("b".to_string())
-    };
-
-    expected_result[6].enumerations = FileEnumeration {
-            platform: Some("linux".to_string()),
-            language: Some("fr".to_string()),
-            user_defined: Some("a".to_string())
-    };
-
-    expected_result[7].enumerations = FileEnumeration {
-            platform: Some("linux".to_string()),
-            language: Some("fr".to_string()),
-            user_defined: Some("b".to_string())
-    };
-
-    let language_list: Vec<String> = vec!["en".to_string(), "fr".to_string()];
-    let platform_list: Vec<String> = vec!["windows".to_string(), "linux".to_string()];
-    let user_list: Vec<String> = vec!["a".to_string(), "b".to_string()];
-
-    assert_eq!(expected_result, FileContext::blank().enumerate(&platform_list, &language_list, &user_list));
-}
\ No newline at end of file
+pub fn zipped_axes_are_paired_up() {
+    let expected_result: Vec<FileContext> = vec![
+        enumerated_file(&[("platform", "windows"), ("toolchain", "msvc"), ("language", "en")]),
+        enumerated_file(&[("platform", "windows"), ("toolchain", "msvc"), ("language", "fr")]),
+        enumerated_file(&[("platform", "linux"), ("toolchain", "gcc"), ("language", "en")]),
+        enumerated_file(&[("platform", "linux"), ("toolchain", "gcc"), ("language", "fr")])
+    ];
+
+    let axes = [test_axis("platform", &["windows", "linux", "mac_os"]), test_axis("language", &["en", "fr"]), test_axis("toolchain", &["msvc", "gcc"])];
+    let zipped_axes = vec![vec!["platform".to_string(), "toolchain".to_string()]];
+
+    assert_eq!(expected_result, FileContext::blank().enumerate(&axes, &zipped_axes));
+    assert_eq!(12, FileContext::blank().enumerate(&axes, &[]).len());
+}
+
+#[test]
+pub fn naming_pattern_can_place_files_in_directories() {
+    let mut file_context = FileContext::blank();
+    file_context.name = "model".to_string();
+    file_context.extension = "cpp".to_string();
+    file_context.naming_pattern = NamingPattern::from_string("[]PLATFORM[]/[]NAME[].[]LANGUAGE[]").unwrap();
+    file_context.enumerations.set("platform", "linux");
+    file_context.enumerations.set("language", "fr");
+
+    assert_eq!("model.fr.cpp", file_context.name_with_extension());
+    assert_eq!("linux/model.fr.cpp", file_context.output_path());
+    assert_eq!("model_linux_fr", file_context.expand_with_enumerations());
+}
@@ -81,18 +81,30 @@ pub fn harvest_files_from_dir(dir: &Option<String>, include_list: &Vec<String>)
     harvested_file_list
 }
 
-pub fn harvest_files_from_dir_as_string(dir: &Option<String>, include_list: &Vec<String>, write_file_names_with_path: bool) -> String {
+// Without a separator every file name is followed by a line ending, with one the separator only goes between file names. 
+pub fn harvest_files_from_dir_as_string(
+    dir: &Option<String>, 
+    include_list: &Vec<String>, 
+    write_file_names_with_path: bool, 
+    separator: Option<&str>) -> String {
     
     let harvested_files = harvest_files_from_dir(dir, include_list);
 
     let mut return_string = String::new();
-    for file in harvested_files {
+    for (index, file) in harvested_files.iter().enumerate() {
+        if index != 0 && separator.is_some() {
+            return_string += separator.unwrap_or_default();
+        }
+
         if write_file_names_with_path {
             return_string += &file.to_fully_expanded_string();
         } else {
             return_string += &file.to_string();
         }
-        return_string += PLATFORM_LINE_ENDING; 
+
+        if separator.is_none() {
+            return_string += PLATFORM_LINE_ENDING; 
+        }
     }
     
     return_string
@@ -55,15 +55,6 @@ pub fn write_file(path: &String, file_contents: &String, overwrite: bool) {
     }
 }
 
-pub fn check_if_file_exists(file: &String) -> bool {
-    let possible_template_file_data = read_to_string(&file);
-    if possible_template_file_data.is_err() {
-        return false;
-    } 
-
-    true
-}
-
 pub fn get_current_dir_name() -> Option<String>{
     let path = std::env::current_dir();
 
@@ -1,6 +1,17 @@
 // This file contains all the functions used to convert to CAPS, Pascal Case, and Camel Case. 
 use log::*;
 
+use crate::suggestion::did_you_mean;
+
+// Every name that can be used to change the case of a string, see format_append_and_remove. 
+pub const FORMATTER_NAMES: &[&str] = &["caps", "upper", "lower", "spaced", "pascal", "camel", "kabob"];
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FormatError {
+    pub message: String,
+    pub suggestion: Option<String>
+}
+
 pub fn string_in_pascal_case(string: &str) -> String {
 
     let mut type_name: String = String::new();
@@ -95,10 +106,12 @@ pub fn subtract_ending_off_string(base: &str, ending: &str) -> Result<String, St
     }
 }
 
-pub fn format_append_and_remove(string: &str, operations: &Vec<String>) -> Option<String> {
+pub fn format_append_and_remove(string: &str, operations: &[String]) -> Result<String, FormatError> {
     if operations.is_empty() {
-        error!("- INTERNAL - Got an empty list of operations for format_append_and_remove. ");
-        return None;
+        return Err(FormatError {
+            message: "- INTERNAL - Got an empty list of operations for format_append_and_remove. ".to_string(),
+            suggestion: None
+        });
     }
 
     info!("Got operations list of: {:?}", operations);
@@ -106,37 +119,43 @@ pub fn format_append_and_remove(string: &str, operations: &Vec<String>) -> Optio
     let mut formatted_string = string.to_string();
     for operation in operations {
         let variable = operation.trim();
+        if variable.is_empty() {
+            continue;
+        }
     
         let first_char = variable.chars().nth(0).unwrap();
         if first_char == '-' {    
             let subtracted_string = subtract_ending_off_string(&formatted_string, &variable[1..]);
             match subtracted_string {
                 Ok(subtracted_string) => formatted_string = subtracted_string,
-                Err(_) => return Some(formatted_string)
+                Err(_) => return Ok(formatted_string)
             }
         } else if first_char == '+' {    
             formatted_string += &variable[1..];
         } else {
             formatted_string = match variable.as_ref() {
                 "caps"   => { string_in_all_caps(&formatted_string) },
+                "upper"  => { string_in_all_caps(&formatted_string) },
                 "lower"  => { string_in_all_lowercase(&formatted_string) },
                 "spaced" => { string_split_into_spaces(&formatted_string) },
                 "pascal" => { string_in_pascal_case(&formatted_string) },
                 "camel"  => { string_in_camel_case(&formatted_string) }, 
                 "kabob"  => { string_in_kebob_case(&formatted_string) }, 
                 _ => {
-                    error!("No recognized formatting method for {{{:}}}. Check documentation for valid formatting methods. ", variable);
-                    return None
+                    return Err(FormatError {
+                        message: format!("No recognized formatting method for {{{:}}}. Check documentation for valid formatting methods. ", variable),
+                        suggestion: did_you_mean(variable, FORMATTER_NAMES)
+                    });
                 }
             }
         }
     }
 
-    Some(formatted_string)
+    Ok(formatted_string)
 }
 
-pub fn format_append_and_remove_but_ensure_formatted_to_type(string: &str, operations: &Vec<String>, default_formatting: String) -> Option<String> {
-    let mut operations = operations.clone();
+pub fn format_append_and_remove_but_ensure_formatted_to_type(string: &str, operations: &[String], default_formatting: String) -> Result<String, FormatError> {
+    let mut operations = operations.to_vec();
     operations.insert(0, default_formatting);
     format_append_and_remove(string, &operations)
 }
\ No newline at end of file
@@ -12,6 +12,16 @@ mod file_context;
 mod config;
 mod logger;
 mod parser;
+mod condition;
+mod template_scope;
+mod diagnostic;
+mod suggestion;
+mod prompt;
+mod data;
+mod table;
+mod naming_pattern;
+mod bundle;
+mod template_manifest;
 
 use program_args::*;
 use file_manip::*;
@@ -19,9 +29,14 @@ use symbol_replacer::*;
 use template_file_list::*;
 use file_context::*;
 use command_line_documentation::print_all_variables;
-use file_harvester::harvest_files_from_dir_as_string;
 use platform_specific::PLATFORM_SEPARATOR_SLASH;
-use config::Config;
+use config::{Config, EnumerationAxis};
+use prompt::*;
+use data::load_data_file;
+use table::Table;
+use naming_pattern::NamingPattern;
+use bundle::{Bundle, BundleFiles};
+use template_manifest::find_templates;
 use logger::Logger;
 use util::*;
 
@@ -45,7 +60,12 @@ fn main() {
         let _ = defualt_config.write(&(exe_location.clone() + PLATFORM_SEPARATOR_SLASH + "config"));
         config = Ok(defualt_config);
     }
-    let config = config.unwrap();
+    let mut config = config.unwrap();
+    if let Err(e) = config.override_user_variables(&args.variables_file, &args.variables) {
+        abort_with_error(&e);
+    }
+
+    let strict = args.strict || config.strict;
 
     info!("Program Args: {:?}", &args);
 
@@ -55,16 +75,27 @@ fn main() {
     }
 
     if args.show_templates {
-        println!("Looking for template files in {{{:}}}", template_dir_path);
-        let file_list = harvest_files_from_dir_as_string(&Some(template_dir_path), &Vec::new(), false);
-        println!("Found the following templates: ");
-        println!("{:}", file_list);
+        print_templates(&template_dir_path, args.verbose_output);
         return;
     }
 
     info!("Using verbose output. ");
     info!("Configuration being used: {:?}", config);
 
+    let prompter = create_prompter(&args);
+    if prompter.is_none() {
+        return;
+    }
+    let mut prompter = prompter.unwrap();
+
+    let data = match &args.data_file {
+        Some(data_file) => match load_data_file(data_file) {
+            Ok(data) => Some(data),
+            Err(e) => abort_with_error(&e)
+        },
+        None => None
+    };
+
     if args.shove_header.is_some() {
 
         let target_file = args.shove_header.unwrap();
@@ -93,10 +124,16 @@ fn main() {
             &header_template.unwrap(), 
             &file_context.unwrap(), 
             &None, 
-            &config);
+            data.as_ref(),
+            &config,
+            &mut prompter);
+
+        if strict && evaluated_header_template.has_errors() {
+            abort_strict_mode(evaluated_header_template.diagnostics.len());
+        }
 
         // @future: Allow the swap of headers by adding a stop point to them. 
-        file_contents = evaluated_header_template + &file_contents;
+        file_contents = evaluated_header_template.text + &file_contents;
 
         write_file(&target_file, &file_contents, true);
 
@@ -108,37 +145,57 @@ fn main() {
         return;
     }
 
+    // Inline enumerations are always used, there's no reason to define one otherwise. 
+    let mut selected_axes = args.enumeration_axes.clone();
+    for inline_enumeration in &args.inline_enumerations {
+        match config.add_inline_enumeration_axis(inline_enumeration) {
+            Ok(axis_name) => selected_axes.push(axis_name),
+            Err(e) => abort_with_error(&e)
+        }
+    }
+
+    let enumeration_axes = match config.selected_enumeration_axes(&selected_axes) {
+        Ok(enumeration_axes) => enumeration_axes,
+        Err(e) => abort_with_error(&e)
+    };
+
+    if let Err(e) = config.enumeration_rules.check(&config.enumeration_axes()) {
+        abort_with_error(&e);
+    }
+
+    if let Some(bundle_name) = &args.bundle {
+        create_bundle(&args, bundle_name, &template_dir_path, &enumeration_axes, data.as_ref(), &mut config, &mut prompter, strict);
+        return;
+    }
+
+    // Why the template couldn't be loaded has already been reported. 
     let template_file = TemplateFile::new(&args.extension_list, &template_dir_path);
     if template_file.is_none() {
-        return;
+        std::process::exit(1);
     }
     let template_file = template_file.unwrap();
+    let template_name = TEMPLATE_FILE_START.to_string() + &template_file.template_file_extension;
+
+    if let Some(manifest) = &template_file.manifest {
+        if let Err(e) = manifest.apply_variables(&mut config.user_variables).and_then(|_| manifest.check_outputs(std::slice::from_ref(&template_name))) {
+            abort_invalid_manifest(&template_file.template_file_path, &e);
+        }
+    }
+
     let forced_file_name: Option<String> = does_template_contain_forced_filename(&template_file.template_file_data);
 
     if forced_file_name.is_some() {
         warn!("File name is being forced to {:}", forced_file_name.clone().unwrap());
     }
 
-    let platform_list:    Vec<String>;
-    if args.create_one_per_platform {
-        platform_list = config.platform_list.clone();
-    } else {
-        platform_list = Vec::new();
+    if forced_file_name.is_some() && args.table_file.is_some() {
+        abort_with_error("Templates that use FORCE_FILE_NAME can't be used with --table, every row would be written to the same file. ");
     }
 
-    let language_list:    Vec<String>;
-    if args.create_one_per_language {
-        language_list = config.language_list.clone();
-    } else {
-        language_list = Vec::new();
-    }
-
-    let enumeration_list: Vec<String>;
-    if args.create_one_per_enumeration {
-        enumeration_list = config.enumeration_list.clone();
-    } else {
-        enumeration_list = Vec::new();
-    }
+    let naming_pattern = match NamingPattern::for_template(&template_file, &config) {
+        Ok(naming_pattern) => naming_pattern,
+        Err(e) => abort_with_error(&e)
+    };
 
     let output_file_description = if forced_file_name.is_some() {
 
@@ -153,11 +210,9 @@ fn main() {
             extension: forced_file_name_extension,
             path: String::new(),
 
-            enumerations: FileEnumeration {
-                platform:    None,
-                language:    None, 
-                user_defined: None
-            }
+            enumerations: FileEnumeration::blank(),
+            variables: std::collections::HashMap::new(),
+            naming_pattern: naming_pattern.clone()
         }
     } else {
         FileContext {
@@ -165,42 +220,327 @@ fn main() {
             extension: if args.file_has_no_extension { String::new() } else { args.extension.clone() },
             path: String::new(),
 
-            enumerations: FileEnumeration {
-                platform:    None,
-                language:    None, 
-                user_defined: None
-            }
+            enumerations: FileEnumeration::blank(),
+            variables: std::collections::HashMap::new(),
+            naming_pattern: naming_pattern.clone()
         }
     };
 
-    let output_file_list = FileContext::enumerate(
-        &output_file_description, 
-        &platform_list, 
-        &language_list, 
-        &enumeration_list
-    );
+    let output_file_descriptions = match file_descriptions(&args, output_file_description) {
+        Ok(output_file_descriptions) => output_file_descriptions,
+        Err(e) => abort_with_error(&e)
+    };
+
+    let (output_file_list, excluded_files) = enumerate_files(&output_file_descriptions, &enumeration_axes, &config);
+
+    // Files with an enumeration value that has its own template, ie: template.linux.cpp, use that one instead. The
+    //  template's own manifest and naming pattern are used for those files, not the ones of the generic template. 
+    let mut enumeration_templates: std::collections::HashMap<String, (TemplateFile, Config)> = std::collections::HashMap::new();
+    let mut file_template_extensions: Vec<String> = Vec::new();
+    for file in &output_file_list {
+        let mut template_extension = TemplateFile::find_for_enumerations(&args.extension_list, &template_dir_path, &file.enumerations)
+            .unwrap_or(template_file.template_file_extension.clone());
+
+        if template_extension != template_file.template_file_extension && !enumeration_templates.contains_key(&template_extension) {
+            match load_enumeration_template(&template_extension, &template_dir_path, &config) {
+                Some(enumeration_template) => { enumeration_templates.insert(template_extension.clone(), enumeration_template); },
+                None => template_extension = template_file.template_file_extension.clone()
+            }
+        }
+        file_template_extensions.push(template_extension);
+    }
+
+    let mut templated_files: Vec<(FileContext, &TemplateFile, &Config)> = Vec::new();
+    for (mut file, template_extension) in output_file_list.into_iter().zip(file_template_extensions) {
+        let (file_template, file_config) = match enumeration_templates.get(&template_extension) {
+            Some((enumeration_template, enumeration_config)) => {
+                info!("Using template {:} for {:}", enumeration_template.template_file_path, file.output_path());
+                file.naming_pattern = match NamingPattern::for_template(enumeration_template, enumeration_config) {
+                    Ok(naming_pattern) => naming_pattern,
+                    Err(e) => abort_with_error(&e)
+                };
+                (enumeration_template, enumeration_config)
+            },
+            None => (&template_file, &config)
+        };
+
+        // The manifest can say that the file is only created under some condition, ie: not for windows. 
+        let file_template_name = TEMPLATE_FILE_START.to_string() + &file_template.template_file_extension;
+        if let Some(condition) = file_template.manifest.as_ref().and_then(|manifest| manifest.output_condition(&file_template_name)) {
+            match check_condition(condition, &file, data.as_ref(), file_config) {
+                Ok(true) => {},
+                Ok(false) => {
+                    info!("Skipping {:}, the condition for it in the manifest isn't met. ", file.output_path());
+                    continue;
+                },
+                Err(e) => {
+                    error!("Failed to check the condition for {:} in its manifest. {:}", file_template_name, e);
+                    return;
+                }
+            }
+        }
+
+        templated_files.push((file, file_template, file_config));
+    }
 
     /*if args.create_matching_header_and_source {
         expanded_list = expand_with_matching_files(&expanded_list); 
     }*/
 
     if args.write_names_of_files_to_screen {
-        for file in output_file_list {
-            println!("{:}", file.name_with_extension());
+        let output_file_list: Vec<FileContext> = templated_files.into_iter().map(|(file, _, _)| file).collect();
+        print_file_matrix(&output_file_list, &excluded_files);
+        return;
+    }
+
+    let mut processed_files: Vec<(FileContext, EvaluatedTemplate)> = Vec::new();
+    for (file, file_template, file_config) in templated_files {
+        let processed_file = replace_symbols(file_template, &file, &args.harvest_directory, data.as_ref(), file_config, &mut prompter);
+        processed_files.push((file, processed_file));
+    }
+
+    write_processed_files(processed_files, 0, &args, strict);
+}
+
+// A template made for an enumeration value along with the configuration it's evaluated with, which has the
+//  variables from its own manifest. Exits the same as for the generic template if they don't match it. 
+fn load_enumeration_template(template_extension: &str, template_dir_path: &String, config: &Config) -> Option<(TemplateFile, Config)> {
+    let enumeration_template = TemplateFile::load(template_extension, template_dir_path, TEMPLATE_FILE_START)?;
+    let template_name = TEMPLATE_FILE_START.to_string() + template_extension;
+
+    let mut enumeration_config = config.clone();
+    if let Some(manifest) = &enumeration_template.manifest {
+        if let Err(e) = manifest.apply_variables(&mut enumeration_config.user_variables).and_then(|_| manifest.check_outputs(std::slice::from_ref(&template_name))) {
+            abort_invalid_manifest(&enumeration_template.template_file_path, &e);
+        }
+    }
+
+    Some((enumeration_template, enumeration_config))
+}
+
+// Every file in the bundle is created for each file described on the command line. 
+#[allow(clippy::too_many_arguments)]
+fn create_bundle(
+    args: &ProgramArguments, 
+    bundle_name: &str, 
+    template_dir_path: &str, 
+    enumeration_axes: &[EnumerationAxis], 
+    data: Option<&serde_json::Value>, 
+    config: &mut Config, 
+    prompter: &mut Prompter, 
+    strict: bool) {
+
+    let bundle = match Bundle::load(template_dir_path, bundle_name) {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            error!("{:}", e);
+            return;
+        }
+    };
+
+    if let Some(manifest) = &bundle.manifest {
+        if let Err(e) = manifest.apply_variables(&mut config.user_variables) {
+            abort_invalid_manifest(&bundle.path, &e);
+        }
+    }
+    let config: &Config = config;
+
+    let mut output_file_description = FileContext::blank();
+    output_file_description.name = args.file_name_without_extension.clone();
+
+    let output_file_descriptions = match file_descriptions(args, output_file_description) {
+        Ok(output_file_descriptions) => output_file_descriptions,
+        Err(e) => abort_with_error(&e)
+    };
+
+    let (output_file_list, excluded_files) = enumerate_files(&output_file_descriptions, enumeration_axes, config);
+
+    // Errors from the bundle's _shared template and file names count towards strict mode too. 
+    let mut error_count: usize = 0;
+    let mut expand_bundle = |files: &[FileContext]| -> Result<BundleFiles, String> {
+        let mut bundle_files: BundleFiles = Vec::new();
+        for file in files {
+            let (expanded_files, expand_error_count) = bundle.expand(file, &args.harvest_directory, data, config, prompter)?;
+            bundle_files.extend(expanded_files);
+            error_count += expand_error_count;
         }
+        Ok(bundle_files)
+    };
+
+    let bundle_files = match expand_bundle(&output_file_list) {
+        Ok(bundle_files) => bundle_files,
+        Err(e) => {
+            error!("{:}", e);
+            return;
+        }
+    };
+
+    // The files that are left out are listed as they were described, expanding them would evaluate _shared for them. 
+    if args.write_names_of_files_to_screen {
+        let files: Vec<FileContext> = bundle_files.into_iter().map(|(file, _)| file).collect();
+        print_file_matrix(&files, &excluded_files);
         return;
     }
-    
-    for file in output_file_list {
-        let processed_file = replace_symbols(&template_file, &file, &args.harvest_directory, &config);
 
-        let file_name = file.name_with_extension(); 
+    let processed_files: Vec<(FileContext, EvaluatedTemplate)> = bundle_files
+        .into_iter()
+        .map(|(file, template)| {
+            let processed_file = replace_symbols(template, &file, &args.harvest_directory, data, config, prompter);
+            (file, processed_file)
+        })
+        .collect();
+
+    write_processed_files(processed_files, error_count, args, strict);
+}
+
+// Everything gets evaluated before anything is written so that strict mode can refuse to write any of it. 
+fn write_processed_files(processed_files: Vec<(FileContext, EvaluatedTemplate)>, other_error_count: usize, args: &ProgramArguments, strict: bool) {
+    let error_count: usize = other_error_count + processed_files.iter().map(|(_, processed_file)| processed_file.diagnostics.len()).sum::<usize>();
+    if strict && error_count > 0 {
+        abort_strict_mode(error_count);
+    }
+
+    for (file, processed_file) in processed_files {
+        let file_name = file.output_path(); 
 
         if args.write_file_to_screen {
             println!("----- {:} -----", file_name);
-            println!("{:}", processed_file);
+            println!("{:}", processed_file.text);
         } else {    
-            write_file(&file_name, &processed_file, args.overwrite);
+            if let Some(directory) = file.directory() {
+                if let Err(e) = std::fs::create_dir_all(&directory) {
+                    error!("Unable to create directory {:} for file {:} reason: {:}", directory, file_name, e);
+                    continue;
+                }
+            }
+            write_file(&file_name, &processed_file.text, args.overwrite);
+        }
+    }
+}
+
+// One file for each row of the table, or just the one from the command line. 
+fn file_descriptions(args: &ProgramArguments, description: FileContext) -> Result<Vec<FileContext>, String> {
+    match &args.table_file {
+        Some(table_file) => table_file_contexts(table_file, &args.name_column, &description),
+        None => Ok(vec![description])
+    }
+}
+
+// Every file the enumerations make, and the files that the exclusions in the configuration file leave out. 
+fn enumerate_files(descriptions: &[FileContext], enumeration_axes: &[EnumerationAxis], config: &Config) -> (Vec<FileContext>, Vec<FileContext>) {
+    let (files, excluded_files): (Vec<FileContext>, Vec<FileContext>) = descriptions
+        .iter()
+        .flat_map(|description| description.enumerate(enumeration_axes, &config.enumeration_rules.zip))
+        .partition(|file| config.enumeration_rules.excluded_by(&file.enumerations).is_none());
+
+    for file in &excluded_files {
+        info!("Leaving out {:}, it matches an exclusion in the configuration file. ", file.output_path());
+    }
+
+    (files, excluded_files)
+}
+
+// Answers from the answers file come first so that the ones passed with --answer can replace them. 
+fn create_prompter(args: &ProgramArguments) -> Option<Prompter> {
+    let mut preset_answers = match &args.answers_file {
+        Some(answers_file) => match load_answers_file(answers_file) {
+            Ok(answers) => answers,
+            Err(e) => {
+                error!("{:}", e);
+                return None;
+            }
+        },
+        None => std::collections::HashMap::new()
+    };
+
+    for answer in &args.answers {
+        match parse_answer(answer) {
+            Ok((question, answer)) => { preset_answers.insert(question, answer); },
+            Err(e) => {
+                error!("{:}", e);
+                return None;
+            }
+        }
+    }
+
+    Some(Prompter::new(preset_answers, !args.no_prompt))
+}
+
+// Each file along with the values it was enumerated with, and the files that the exclusions left out. 
+fn print_file_matrix(files: &[FileContext], excluded_files: &[FileContext]) {
+    let path_width = files.iter().chain(excluded_files).map(|file| file.output_path().len()).max().unwrap_or(0);
+
+    for file in files {
+        println!("{:}", file_matrix_line(file, path_width));
+    }
+
+    if !excluded_files.is_empty() {
+        println!("Left out by the exclusions in the configuration file: ");
+        for file in excluded_files {
+            println!("{:}", file_matrix_line(file, path_width));
+        }
+    }
+}
+
+fn file_matrix_line(file: &FileContext, path_width: usize) -> String {
+    let values: Vec<String> = file.enumerations.axis_values().map(|(axis, value)| format!("{:}={:}", axis, value)).collect();
+    if values.is_empty() {
+        return file.output_path();
+    }
+
+    format!("{:<width$}  {:}", file.output_path(), values.join(" "), width = path_width)
+}
+
+// Every template and bundle, along with the description and aliases from its manifest. 
+// Only the names of templates are listed unless verbose is on, since editor plugins read each line as a template's name. 
+fn print_templates(template_dir_path: &str, verbose: bool) {
+    println!("Looking for template files in {{{:}}}", template_dir_path);
+    println!("Found the following templates: ");
+
+    let templates = find_templates(template_dir_path);
+    if !verbose {
+        for (name, _) in templates.iter().filter(|(name, _)| !name.ends_with('/')) {
+            println!("{:}", name);
         }
+        return;
+    }
+
+    let name_width = templates.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, manifest) in templates {
+        let description = match manifest {
+            Some(manifest) if manifest.aliases.is_empty() => manifest.description.unwrap_or_default(),
+            Some(manifest) => format!("{:} (also {:})", manifest.description.unwrap_or_default(), manifest.aliases.join(", ")).trim().to_string(),
+            None => String::new()
+        };
+
+        println!("{:}", format!("{:<width$}  {:}", name, description, width = name_width).trim_end());
     }
+}
+
+fn table_file_contexts(table_file: &str, name_column: &Option<String>, description: &FileContext) -> Result<Vec<FileContext>, String> {
+    let table = Table::load(table_file)?;
+    let name_column = table.name_column(name_column)?;
+
+    if table.rows.is_empty() {
+        warn!("Table {:} has no rows, no files will be created. ", table_file);
+    }
+
+    table.file_contexts(&name_column, description)
+}
+
+fn abort_strict_mode(error_count: usize) -> ! {
+    error!("Found {:} error(s) while evaluating templates. Nothing was written since strict mode is on. ", error_count);
+    std::process::exit(1);
+}
+
+// Bad input is reported with an error code so that scripts can tell nothing was created. 
+fn abort_with_error(e: &str) -> ! {
+    error!("{:}", e);
+    std::process::exit(1);
+}
+
+// Input that doesn't match a manifest fails the same as strict mode does, so scripts can tell nothing was created. 
+fn abort_invalid_manifest(template_path: &str, e: &str) -> ! {
+    error!("{:} {:}", template_path, e);
+    std::process::exit(1);
 }
\ No newline at end of file
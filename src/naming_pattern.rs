@@ -0,0 +1,245 @@
+use log::*;
+
+use crate::config::{Config, EnumerationAxis};
+use crate::file_context::FileEnumeration;
+use crate::formatter::format_append_and_remove;
+use crate::parser::*;
+use crate::suggestion::did_you_mean;
+use crate::template_file_list::TemplateFile;
+
+// How enumerated files are named, built from []NAME[], a token for any enumeration axis, ie: []PLATFORM[],
+//  and []ENUMERATIONS{separator}[] for every axis that isn't placed on its own. Anything else is placed
+//  as is, so "[]PLATFORM[]/[]NAME[]" puts each platform in its own directory. When a file wasn't enumerated
+//  over an axis, its token is left out along with the text in front of it, or the text after it if
+//  it's the first thing with a value.
+pub const DEFAULT_NAMING_PATTERN: &str = "[]NAME[]_[]ENUMERATIONS{_}[]";
+
+// Tokens a naming pattern has besides the ones for the axes.
+pub const NAMING_PATTERN_TOKENS: &[&str] = &["NAME", "ENUMERATIONS"];
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NamingPattern {
+    segments: Vec<NamingSegment>
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum NamingSegment {
+    Text(String),
+    Name(Vec<String>),
+    Axis(String, Vec<String>),
+    Enumerations(String, Vec<String>)
+}
+
+impl NamingPattern {
+    pub fn from_string(text: &str) -> Result<Self, String> {
+        Self::from_nodes(&Parser::parse(text))
+    }
+
+    pub fn from_nodes(nodes: &[TemplateNode]) -> Result<Self, String> {
+        let mut segments: Vec<NamingSegment> = Vec::new();
+        for node in nodes {
+            let segment = match node {
+                TemplateNode::Text(text) => NamingSegment::Text(text.clone()),
+                TemplateNode::Token(token) => naming_segment(token)?,
+                TemplateNode::Invalid(invalid_token) => return Err(format!("{:} in the naming pattern is not a valid token. {:}", invalid_token.text, invalid_token.error)),
+                TemplateNode::Included(_) => return Err("Naming patterns can't include other templates. ".to_string())
+            };
+            segments.push(segment);
+        }
+
+        if !segments.iter().any(|segment| matches!(segment, NamingSegment::Name(_))) {
+            return Err("A naming pattern needs []NAME[] in it, otherwise every file would have the same name. ".to_string());
+        }
+
+        Ok(Self { segments })
+    }
+
+    // A template's own NAMING_PATTERN wins over the one in the configuration file. 
+    pub fn for_template(template_file: &TemplateFile, config: &Config) -> Result<Self, String> {
+        let naming_pattern = match (&template_file.naming_pattern, &config.naming_pattern) {
+            (Some(naming_pattern), _) => naming_pattern.clone(),
+            (None, Some(naming_pattern)) => Self::from_string(naming_pattern)
+                .map_err(|e| format!("Bad naming_pattern in the configuration file. {:}", e))?,
+            (None, None) => Self::default()
+        };
+
+        naming_pattern.check_axes(&config.enumeration_axes())?;
+
+        Ok(naming_pattern)
+    }
+
+    // The same pattern with every file placed inside of directory. 
+    pub fn in_directory(&self, directory: &str) -> Self {
+        let mut segments = vec![NamingSegment::Text(directory.trim_end_matches('/').to_string() + "/")];
+        segments.extend(self.segments.iter().cloned());
+
+        Self { segments }
+    }
+
+    // Axis tokens can only be checked once we know which axes there are.
+    pub fn check_axes(&self, axes: &[EnumerationAxis]) -> Result<(), String> {
+        let token_ids: Vec<String> = axes.iter().map(|axis| axis.token_id()).collect();
+        for segment in &self.segments {
+            if let NamingSegment::Axis(token_id, _) = segment {
+                if !token_ids.contains(token_id) {
+                    let suggestion = match did_you_mean(token_id, &token_ids) {
+                        Some(suggestion) => format!(" Did you mean {:}?", suggestion),
+                        None => String::new()
+                    };
+                    return Err(format!("{:} in the naming pattern is not an enumeration axis.{:}", token_id, suggestion));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // The file name without its extension. Directories are seperated with a /
+    pub fn expand(&self, name: &str, enumerations: &FileEnumeration) -> String {
+        let placed_axes: Vec<&String> = self.segments.iter().filter_map(|segment| match segment {
+            NamingSegment::Axis(token_id, _) => Some(token_id),
+            _ => None
+        }).collect();
+
+        let mut expanded_segments: Vec<ExpandedSegment> = Vec::new();
+        let mut drop_next_text = false;
+        for segment in &self.segments {
+            let value = match segment {
+                NamingSegment::Text(text) => {
+                    if !drop_next_text {
+                        expanded_segments.push(ExpandedSegment::Text(text.clone()));
+                    }
+                    drop_next_text = false;
+                    continue;
+                },
+                NamingSegment::Name(filters) => Some(apply_filters(name, filters)),
+                NamingSegment::Axis(token_id, filters) => enumerations
+                    .axis_values()
+                    .find(|(axis, _)| &axis.to_uppercase() == token_id)
+                    .map(|(_, value)| apply_filters(value, filters)),
+                NamingSegment::Enumerations(separator, filters) => {
+                    let values: Vec<String> = enumerations
+                        .axis_values()
+                        .filter(|(axis, _)| !placed_axes.contains(&&axis.to_uppercase()))
+                        .map(|(_, value)| apply_filters(value, filters))
+                        .collect();
+                    if values.is_empty() { None } else { Some(values.join(separator)) }
+                }
+            };
+
+            match value {
+                Some(value) => {
+                    drop_next_text = false;
+                    expanded_segments.push(ExpandedSegment::Value(value));
+                },
+                None => {
+                    let has_value_before = expanded_segments.iter().any(|segment| matches!(segment, ExpandedSegment::Value(_)));
+                    if !has_value_before {
+                        drop_next_text = true;
+                    } else if let Some(ExpandedSegment::Text(_)) = expanded_segments.last() {
+                        expanded_segments.pop();
+                    }
+                }
+            }
+        }
+
+        expanded_segments.into_iter().map(|segment| match segment {
+            ExpandedSegment::Text(text) | ExpandedSegment::Value(text) => text
+        }).collect()
+    }
+}
+
+impl Default for NamingPattern {
+    fn default() -> Self {
+        Self::from_string(DEFAULT_NAMING_PATTERN).unwrap()
+    }
+}
+
+enum ExpandedSegment {
+    Text(String),
+    Value(String)
+}
+
+fn naming_segment(token: &TokenNode) -> Result<NamingSegment, String> {
+    let filters = token.filters.clone();
+    match (token.id.as_ref(), token.parameters.len()) {
+        ("NAME", 0) => Ok(NamingSegment::Name(filters)),
+        ("ENUMERATIONS", 0) => Ok(NamingSegment::Enumerations("_".to_string(), filters)),
+        ("ENUMERATIONS", 1) => match &token.parameters[0][..] {
+            [] => Ok(NamingSegment::Enumerations(String::new(), filters)),
+            [TemplateNode::Text(separator)] => Ok(NamingSegment::Enumerations(separator.clone(), filters)),
+            _ => Err("The separator for ENUMERATIONS in a naming pattern has to be plain text. ".to_string())
+        },
+        (id, 0) => Ok(NamingSegment::Axis(id.to_string(), filters)),
+        (id, _) => Err(format!("{:} in the naming pattern doesn't take parameters. ", id))
+    }
+}
+
+// Filters work the same as they do in a template, ie: []PLATFORM|caps[]
+fn apply_filters(value: &str, filters: &[String]) -> String {
+    if filters.is_empty() {
+        return value.to_string();
+    }
+
+    match format_append_and_remove(value, filters) {
+        Ok(formatted_value) => formatted_value,
+        Err(error) => {
+            error!("Failed to apply filters in the naming pattern. {:}", error.message);
+            value.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_enumerations(axis_values: &[(&str, &str)]) -> FileEnumeration {
+    let mut enumerations = FileEnumeration::blank();
+    for (axis, value) in axis_values {
+        enumerations.set(axis, value);
+    }
+    enumerations
+}
+
+#[test]
+fn default_pattern_appends_every_value() {
+    let pattern = NamingPattern::default();
+
+    assert_eq!("model", pattern.expand("model", &test_enumerations(&[])));
+    assert_eq!("model_linux_fr", pattern.expand("model", &test_enumerations(&[("platform", "linux"), ("language", "fr")])));
+}
+
+#[test]
+fn pattern_controls_separator_order_and_placement() {
+    let enumerations = test_enumerations(&[("platform", "linux"), ("language", "fr")]);
+
+    assert_eq!("model.fr.linux", NamingPattern::from_string("[]NAME[].[]LANGUAGE[].[]PLATFORM[]").unwrap().expand("model", &enumerations));
+    assert_eq!("linux/model-fr", NamingPattern::from_string("[]PLATFORM[]/[]NAME[]-[]ENUMERATIONS{-}[]").unwrap().expand("model", &enumerations));
+    assert_eq!("src/LINUX/model", NamingPattern::from_string("src/[]PLATFORM|caps[]/[]NAME[]").unwrap().expand("model", &enumerations));
+}
+
+#[test]
+fn missing_values_take_their_separator_with_them() {
+    let pattern = NamingPattern::from_string("[]PLATFORM[]/[]LANGUAGE[]/[]NAME[]_[]BOARD[]_[]ENUMERATION[]").unwrap();
+
+    assert_eq!("model", pattern.expand("model", &test_enumerations(&[])));
+    assert_eq!("fr/model_a", pattern.expand("model", &test_enumerations(&[("language", "fr"), ("enumeration", "a")])));
+    assert_eq!("linux/model_stm32", pattern.expand("model", &test_enumerations(&[("platform", "linux"), ("board", "stm32")])));
+}
+
+#[test]
+fn patterns_can_be_placed_in_a_directory() {
+    let pattern = NamingPattern::from_string("[]PLATFORM[]/[]NAME[]").unwrap().in_directory("src/");
+
+    assert_eq!("src/model", pattern.expand("model", &test_enumerations(&[])));
+    assert_eq!("src/linux/model", pattern.expand("model", &test_enumerations(&[("platform", "linux")])));
+}
+
+#[test]
+fn bad_patterns_are_errors() {
+    assert!(NamingPattern::from_string("[]PLATFORM[]").is_err());
+    assert!(NamingPattern::from_string("[]NAME[]_[]PLATFORM{x}[]").is_err());
+
+    let axes = vec![EnumerationAxis::new("platform", &Vec::new())];
+    assert_eq!(
+        Err("PLATFROM in the naming pattern is not an enumeration axis. Did you mean PLATFORM?".to_string()),
+        NamingPattern::from_string("[]NAME[].[]PLATFROM[]").unwrap().check_axes(&axes));
+}
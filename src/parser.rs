@@ -1,5 +1,7 @@
 use log::*;
 
+use crate::token::{Token, TokenParseError};
+
 // Regex wasent cutting it, and this is pretty simple parsing
 pub struct Parser;
 
@@ -7,9 +9,173 @@ pub struct Parser;
 pub struct FoundToken {
     pub start: usize,
     pub end: usize, 
+    pub span: Span
+}
+
+// Line and column are both counted from 1, columns are counted in characters. 
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize
+}
+
+// The end is the position right after the last character. 
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position
+}
+
+// A template gets parsed once into a list of these, anything that isn't a token is 
+//  kept as text. Each parameter of a token is parsed the same way so that tokens
+//  can be nested inside of other tokens. 
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum TemplateNode {
+    Text(String),
+    Token(TokenNode),
+    Invalid(InvalidToken),
+    Included(IncludedTemplate)
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct TokenNode {
+    pub id: String,
+    pub parameters: Vec<Vec<TemplateNode>>,
+    pub parameter_positions: Vec<Position>,
+    pub filters: Vec<String>,
+    pub span: Span
+}
+
+// Nodes that were parsed from another template file, like the parent of a template 
+//  that uses EXTENDS. Positions inside of nodes are in path's text. 
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct IncludedTemplate {
+    pub path: String,
+    pub text: String,
+    pub nodes: Vec<TemplateNode>
+}
+
+// Something that looked like a token but couldn't be parsed as one. This gets reported 
+//  when the template is evaluated, so that it gets reported with everything else. 
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct InvalidToken {
+    pub text: String,
+    pub error: TokenParseError,
+    pub span: Span
+}
+
+impl Position {
+    pub fn start() -> Self {
+        Self {
+            line: 1,
+            column: 1
+        }
+    }
+
+    // Treats relative as a position inside of a string that starts at self. 
+    pub fn offset_by(&self, relative: Position) -> Self {
+        if relative.line == 1 {
+            Self {
+                line: self.line,
+                column: self.column + relative.column - 1
+            }
+        } else {
+            Self {
+                line: self.line + relative.line - 1,
+                column: relative.column
+            }
+        }
+    }
+
+    // The position right after text, if text started at self. 
+    pub fn advanced_by(&self, text: &str) -> Self {
+        let mut position = *self;
+        for character in text.chars() {
+            if character == '\n' {
+                position.line += 1;
+                position.column = 1;
+            } else {
+                position.column += 1;
+            }
+        }
+
+        position
+    }
 }
 
 impl Parser {
+    pub fn parse(string: &str) -> Vec<TemplateNode> {
+        Parser::parse_from(string, Position::start())
+    }
+
+    // Same as parse, but string starts at start_position in the template. This is how 
+    //  tokens inside of parameters know where they are in the file. 
+    pub fn parse_from(string: &str, start_position: Position) -> Vec<TemplateNode> {
+        let mut nodes: Vec<TemplateNode> = Vec::new();
+
+        let mut remaining = string;
+        let mut remaining_position = start_position;
+        while let Some(found_token) = Parser::find_first_token(remaining) {
+            if found_token.start != 0 {
+                nodes.push(TemplateNode::Text(remaining[..found_token.start].to_string()));
+            }
+
+            let token_text = &remaining[found_token.start..found_token.end];
+            let span = Span {
+                start: remaining_position.offset_by(found_token.span.start),
+                end:   remaining_position.offset_by(found_token.span.end)
+            };
+
+            match Token::from_string_with_filters(token_text) {
+                Ok((token, filters)) => nodes.push(TemplateNode::Token(Parser::build_token_node(token, filters, token_text, span))),
+                Err(error) => {
+                    info!("Failed to parse token {:}. {:}", token_text, error);
+                    nodes.push(TemplateNode::Invalid(InvalidToken {
+                        text: token_text.to_string(),
+                        error,
+                        span
+                    }));
+                }
+            }
+
+            remaining = &remaining[found_token.end..];
+            remaining_position = span.end;
+        }
+
+        if !remaining.is_empty() {
+            nodes.push(TemplateNode::Text(remaining.to_string()));
+        }
+
+        nodes
+    }
+
+    fn build_token_node(token: Token, filters: Vec<String>, token_text: &str, span: Span) -> TokenNode {
+        let mut parameters: Vec<Vec<TemplateNode>> = Vec::new();
+        let mut parameter_positions: Vec<Position> = Vec::new();
+
+        if let Some(variables) = &token.variables {
+            // Parameters are written back to back after the first '{' with "|||" between them. 
+            let mut parameter_offset = token_text.find('{').unwrap_or(0) + 1;
+            for variable in variables {
+                let parameter_text = variable.rebuild_string();
+                let parameter_position = span.start.advanced_by(&token_text[..parameter_offset]);
+
+                parameters.push(Parser::parse_from(&parameter_text, parameter_position));
+                parameter_positions.push(parameter_position);
+
+                parameter_offset += parameter_text.len() + "|||".len();
+            }
+        }
+
+        TokenNode {
+            id: token.id,
+            parameters,
+            parameter_positions,
+            filters,
+            span
+        }
+    }
+
     #[allow(dead_code)]
     pub fn find_all_tokens(string: &str) -> Vec<FoundToken> {
         let mut start_index: usize = 0;
@@ -41,20 +207,31 @@ impl Parser {
         //  When we get into the parameter section of the variable {} we count the 
         //  number of open curlies we get so that we can allow the user to use {} 
         //  in their variables.                                 - Austin Haskell  
+        //  A '|' right after the name or the parameters starts the filters, which 
+        //  run until the closing square brackets. 
         let mut open_square_bracket_found: bool = false;
         let mut is_in_token: bool = false;
         let mut is_in_parameters: bool = false;
+        let mut is_in_filters: bool = false;
+        let mut previous_character: char = ' ';
 
         let mut user_curly_brackets_count: usize = 0;
 
         let mut start_index: usize = 0;
 
-        let mut index: usize = 0;
-        for character in string.chars() {
+        // Tracked alongside the index so that the token can say where it is. 
+        let mut position = Position::start();
+        let mut start_position = Position::start();
+
+        // Indices are byte offsets so that they can be used to slice the string. 
+        for (index, character) in string.char_indices() {
             match character {
                 '{' => { 
                     if is_in_parameters {
                         user_curly_brackets_count+=1;
+                    } else if is_in_filters {
+                        is_in_token = false;
+                        is_in_filters = false;
                     } else {
                         if is_in_token {
                             is_in_parameters = true;
@@ -74,6 +251,7 @@ impl Parser {
                     open_square_bracket_found = true; 
                     if !is_in_token {
                         start_index = index;
+                        start_position = position;
                     }
                 },
                 ']' => { 
@@ -82,21 +260,40 @@ impl Parser {
                             info!("Found token!!!! S:{:}, E:{:}", start_index, index + 1);
                             return Some(FoundToken {
                                 start: start_index,
-                                end: index + 1
+                                end: index + 1,
+                                span: Span {
+                                    start: start_position,
+                                    end: position.advanced_by("]")
+                                }
                             });
                         } else {
                             is_in_token = true;
+                            is_in_filters = false;
                         }
                     } else {
                         if !is_in_parameters {
                             open_square_bracket_found = false;
                             is_in_token = false;
+                            is_in_filters = false;
                         }
                     }
                 },
+                '|' if is_in_token && !is_in_parameters && !is_in_filters => {
+                    if previous_character.is_alphabetic() || previous_character == '_' || previous_character == '}' {
+                        is_in_filters = true;
+                    } else {
+                        is_in_token = false;
+                    }
+                    open_square_bracket_found = false;
+                },
                 _ => {
 
-                    if !(character.is_alphabetic() || character == '_') && is_in_token && !is_in_parameters {
+                    if is_in_filters {
+                        if character.is_whitespace() {
+                            is_in_token = false;
+                            is_in_filters = false;
+                        }
+                    } else if !(character.is_alphabetic() || character == '_') && is_in_token && !is_in_parameters {
                         is_in_token = false;
                     }
 
@@ -104,7 +301,9 @@ impl Parser {
                     open_square_bracket_found = false;
                 }
             }
-            index += 1;
+
+            position = position.advanced_by(&string[index..index + character.len_utf8()]);
+            previous_character = character;
         }
 
         None
@@ -131,7 +330,11 @@ fn token_with_no_parameters() {
 
     assert_eq!(vec![FoundToken {
         start: 0,
-        end: 21
+        end: 21,
+        span: Span {
+            start: Position { line: 1, column: 1 },
+            end:   Position { line: 1, column: 22 }
+        }
     }], Parser::find_all_tokens(test_string))
 }
 
@@ -141,7 +344,11 @@ fn token_with_no_parameters_but_with_curly_brackets() {
 
     assert_eq!(vec![FoundToken {
         start: 0,   
-        end: 23
+        end: 23,
+        span: Span {
+            start: Position { line: 1, column: 1 },
+            end:   Position { line: 1, column: 24 }
+        }
     }], Parser::find_all_tokens(test_string))
 }
 
@@ -167,7 +374,11 @@ fn token_with_sub_tokens_parses() {
     
     assert_eq!(vec![ FoundToken {
         start: 23,
-        end: 93 + 23
+        end: 93 + 23,
+        span: Span {
+            start: Position { line: 1, column: 24 },
+            end:   Position { line: 2, column: 61 }
+        }
     }], found_tokens);
 }
 
@@ -181,6 +392,98 @@ fn token_with_sub_tokens_and_brackets_parses() {
     
     assert_eq!(vec![ FoundToken {
         start: 23,
-        end: 95 + 23
+        end: 95 + 23,
+        span: Span {
+            start: Position { line: 1, column: 24 },
+            end:   Position { line: 2, column: 63 }
+        }
     }], found_tokens);
-}
\ No newline at end of file
+}
+
+#[test]
+fn token_after_multi_byte_characters_parses() {
+    let test_string = "© 2022 []USER[]";
+
+    let found_token = Parser::find_first_token(test_string).unwrap();
+
+    assert_eq!("[]USER[]", &test_string[found_token.start..found_token.end]);
+}
+
+#[test]
+fn parse_splits_text_and_nested_tokens() {
+    let test_string = "class []FILE_NAME_AS_TYPE[] {[]IF{PLATFORM|||[]PLATFORM[]}[]}";
+
+    let position = |line, column| Position { line, column };
+
+    assert_eq!(vec![
+        TemplateNode::Text("class ".to_string()),
+        TemplateNode::Token(TokenNode {
+            id: "FILE_NAME_AS_TYPE".to_string(),
+            parameters: Vec::new(),
+            parameter_positions: Vec::new(),
+            filters: Vec::new(),
+            span: Span { start: position(1, 7), end: position(1, 28) }
+        }),
+        TemplateNode::Text(" {".to_string()),
+        TemplateNode::Token(TokenNode {
+            id: "IF".to_string(),
+            parameters: vec![
+                vec![TemplateNode::Text("PLATFORM".to_string())],
+                vec![TemplateNode::Token(TokenNode {
+                    id: "PLATFORM".to_string(),
+                    parameters: Vec::new(),
+                    parameter_positions: Vec::new(),
+                    filters: Vec::new(),
+                    span: Span { start: position(1, 46), end: position(1, 58) }
+                })]
+            ],
+            parameter_positions: vec![position(1, 35), position(1, 46)],
+            filters: Vec::new(),
+            span: Span { start: position(1, 30), end: position(1, 61) }
+        }),
+        TemplateNode::Text("}".to_string())
+    ], Parser::parse(test_string));
+}
+
+#[test]
+fn parse_tracks_positions_across_lines() {
+    let test_string = "line one\n  []FOR_EACH_FILE_IN_DIR{h|||\n    []FILE_NAME[]}[] []nope[]";
+
+    let nodes = Parser::parse(test_string);
+
+    let for_each = match &nodes[1] { TemplateNode::Token(token) => token, _ => panic!("Expected a token") };
+    assert_eq!(Position { line: 2, column: 3 }, for_each.span.start);
+    assert_eq!(Position { line: 3, column: 21 }, for_each.span.end);
+
+    let file_name = match &for_each.parameters[1][1] { TemplateNode::Token(token) => token, _ => panic!("Expected a token") };
+    assert_eq!(Position { line: 3, column: 5 }, file_name.span.start);
+
+    match &nodes[3] {
+        TemplateNode::Invalid(invalid) => assert_eq!(Position { line: 3, column: 22 }, invalid.span.start),
+        _ => panic!("Expected an invalid token")
+    }
+}
+#[test]
+fn token_with_filters() {
+    let test_string = "#define []DIR|caps|+_H[] and []USER_VAR{TEAM}|kabob[]";
+
+    let filters: Vec<(String, Vec<String>)> = Parser::parse(test_string)
+        .into_iter()
+        .filter_map(|node| match node {
+            TemplateNode::Token(token) => Some((token.id, token.filters)),
+            _ => None
+        })
+        .collect();
+
+    assert_eq!(vec![
+        ("DIR".to_string(), vec!["caps".to_string(), "+_H".to_string()]),
+        ("USER_VAR".to_string(), vec!["kabob".to_string()])
+    ], filters);
+}
+
+#[test]
+fn pipes_that_are_not_filters_are_left_alone() {
+    let test_string = "a[] || b[] []|x[]";
+
+    assert!(Parser::find_all_tokens(test_string).is_empty());
+}
@@ -1,5 +1,7 @@
 use clap::{Arg, App};
 
+use crate::config::{PLATFORM_AXIS, LANGUAGE_AXIS, ENUMERATION_AXIS};
+
 #[derive(Debug)]
 pub struct ProgramArguments {
     pub template_file: String,
@@ -13,13 +15,13 @@ pub struct ProgramArguments {
     pub use_explicit_template_file: bool,
 
     pub create_matching_header_and_source: bool,
-    pub create_one_per_platform: bool,
-    pub create_one_per_enumeration: bool,
-    pub create_one_per_language: bool,
+    pub enumeration_axes: Vec<String>,
+    pub inline_enumerations: Vec<String>,
     pub create_blank: bool,
 
     pub overwrite: bool,
     pub verbose_output: bool,
+    pub strict: bool,
 
     pub write_file_to_screen: bool,
     pub write_names_of_files_to_screen: bool,
@@ -28,12 +30,28 @@ pub struct ProgramArguments {
     pub show_templates: bool,
 
     pub harvest_directory: Option<String>,
+    pub bundle: Option<String>,
+    pub data_file: Option<String>,
+
+    pub table_file: Option<String>,
+    pub name_column: Option<String>,
+
+    pub variables: Vec<String>,
+    pub variables_file: Option<String>,
+
+    pub answers: Vec<String>,
+    pub answers_file: Option<String>,
+    pub no_prompt: bool,
 
     pub shove_header: Option<String>
 }
 
 impl ProgramArguments {
     pub fn create() -> Self {
+        Self::from_args(std::env::args())
+    }
+
+    fn from_args<I: IntoIterator<Item = String>>(program_args: I) -> Self {
 
         let args = App::new("Template Creation Tool")
                     .version(env!("CARGO_PKG_VERSION"))
@@ -75,16 +93,49 @@ A full list of the variables supported can be found on the github page for this
                         .short("p")
                         .long("platform")
                         .help("If present, will create one file per item on the platform list"))
+                    .arg(
+                        Arg::with_name("platform_values")
+                        .long("platforms")
+                        .takes_value(true)
+                        .help("The same as -p but only with the values given, ie: --platforms linux,mac_os, values starting with ! are left out. "))
                     .arg(
                         Arg::with_name("enumeration")
                         .short("e")
                         .long("enumeration")
                         .help("If present, will create one file per item on the enumeration list"))
+                    .arg(
+                        Arg::with_name("enumeration_values")
+                        .long("enumerations")
+                        .takes_value(true)
+                        .help("The same as -e but only with the values given, ie: --enumerations a,b, values starting with ! are left out. "))
                     .arg(
                         Arg::with_name("language")
                         .short("l")
                         .long("language")
                         .help("If present, will create one file per item on the language list"))
+                    .arg(
+                        Arg::with_name("language_values")
+                        .long("languages")
+                        .takes_value(true)
+                        .help("The same as -l but only with the values given, ie: --languages en,fr, values starting with ! are left out. "))
+                    .arg(
+                        Arg::with_name("axis")
+                        .long("axis")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Creates one file per item on the named enumeration axis from the configuration file. Can be used more than once, -p, -l and -e are the same as --axis platform, language and enumeration. Takes the same value list as --platforms, ie: --axis board=stm32,esp32 "))
+                    .arg(
+                        Arg::with_name("enum")
+                        .long("enum")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Creates one file per value of an enumeration that only exists for this run, ie: --enum board=a,b,c "))
+                    .arg(
+                        Arg::with_name("strict")
+                        .long("strict")
+                        .help("If present, every file is evaluated before anything is written. If any variable fails to evaluate nothing is written and tt exits with an error code. "))
                     .arg(
                         Arg::with_name("matching_headers")
                         .short("m")
@@ -115,7 +166,7 @@ A full list of the variables supported can be found on the github page for this
                         Arg::with_name("show_templates")
                         .short("z")
                         .long("show_templates")
-                        .help("Lists the names of all template files found as well as the directory being used for templates.")
+                        .help("Lists the names of all template files found as well as the directory being used for templates. With -v bundles are listed too, along with the descriptions from their manifests.")
                     )
                     .arg(
                         Arg::with_name("header")
@@ -130,7 +181,65 @@ A full list of the variables supported can be found on the github page for this
                         .long("harvest")
                         .takes_value(true)
                         .help("Specifies the harvest directory. This is the directory that will be used for []FOR_EACH_FILE_IN_DIR{}[] and []EACH_FILE_IN_DIR[]. If this isn't present, the current working directory will be used. Currently if you use this argument, all file paths will be generated with the absolute path to that file. ")
-                    ).get_matches();
+                    )
+                    .arg(
+                        Arg::with_name("bundle")
+                        .long("bundle")
+                        .takes_value(true)
+                        .help("Creates every file in a template bundle, a directory in the templates directory, instead of a single file. The name passed with -f or -d is used to name the files. ")
+                    )
+                    .arg(
+                        Arg::with_name("data")
+                        .long("data")
+                        .takes_value(true)
+                        .help("A JSON file that []DATA{}[] reads values from and []FOR_EACH_ITEM{}[] loops over. ")
+                    )
+                    .arg(
+                        Arg::with_name("table")
+                        .long("table")
+                        .takes_value(true)
+                        .help("A CSV or JSON table to create one file per row from. Files are named after the name column and every column can be used with []TEMPLATE_VAR{}[]. ")
+                    )
+                    .arg(
+                        Arg::with_name("name_column")
+                        .long("name_column")
+                        .takes_value(true)
+                        .requires("table")
+                        .help("The column of the table that files are named after. Defaults to a column called name, or the first column if there isn't one. ")
+                    )
+                    .arg(
+                        Arg::with_name("var")
+                        .long("var")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Sets a variable for []USER_VAR{}[] written as NAME=VALUE, taking precedence over the configuration file. Can be used more than once. ")
+                    )
+                    .arg(
+                        Arg::with_name("vars_file")
+                        .long("vars-file")
+                        .takes_value(true)
+                        .help("A JSON file of variable name to value used the same as --var. Variables passed with --var take precedence. ")
+                    )
+                    .arg(
+                        Arg::with_name("answer")
+                        .long("answer")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Answers a []PROMPT{}[] ahead of time, written as \"question=answer\". Can be used more than once. ")
+                    )
+                    .arg(
+                        Arg::with_name("answers_file")
+                        .long("answers_file")
+                        .takes_value(true)
+                        .help("A JSON file of question to answer used to answer []PROMPT{}[] ahead of time. Answers passed with --answer take precedence. ")
+                    )
+                    .arg(
+                        Arg::with_name("no_prompt")
+                        .long("no_prompt")
+                        .help("Never ask for answers to []PROMPT{}[], prompts that weren't answered ahead of time use their default. This is always the case when tt isn't ran from a terminal. ")
+                    ).get_matches_from(program_args);
 
         let template_name = args.value_of("template_file").unwrap_or("");
 
@@ -157,6 +266,15 @@ A full list of the variables supported can be found on the github page for this
             extension_list = file_name.split('.').collect();
         }
         
+        let mut enumeration_axes: Vec<String> = args.values_of("axis").map(|axes| axes.map(|axis| axis.to_string()).collect()).unwrap_or_default();
+        for (flag, axis) in [("platform", PLATFORM_AXIS), ("language", LANGUAGE_AXIS), ("enumeration", ENUMERATION_AXIS)] {
+            match args.value_of(flag.to_string() + "_values") {
+                Some(values) => enumeration_axes.push(format!("{:}={:}", axis, values)),
+                None if args.is_present(flag) => enumeration_axes.push(axis.to_string()),
+                None => {}
+            }
+        }
+        
         ProgramArguments {
             file_name: file_name.clone(),
             template_file: template_name.to_string(),
@@ -168,13 +286,13 @@ A full list of the variables supported can be found on the github page for this
             use_explicit_template_file:        args.is_present("template_file"),
             create_matching_header_and_source: args.is_present("matching_headers"),
 
-            create_one_per_platform:    args.is_present("platform"),
-            create_one_per_enumeration: args.is_present("enumeration"),
-            create_one_per_language:    args.is_present("language"),
+            enumeration_axes,
+            inline_enumerations: args.values_of("enum").map(|enumerations| enumerations.map(|enumeration| enumeration.to_string()).collect()).unwrap_or_default(),
             create_blank:               args.is_present("blank"),
             
             overwrite:            args.is_present("overwrite"),
             verbose_output:       args.is_present("verbose"),
+            strict:               args.is_present("strict"),
 
             write_file_to_screen: args.is_present("debug"),
             write_names_of_files_to_screen: args.is_present("list_names"),
@@ -183,10 +301,34 @@ A full list of the variables supported can be found on the github page for this
             show_templates: args.is_present("show_templates"),
 
             harvest_directory: harvest_directory,
+            bundle: args.value_of("bundle").map(|bundle| bundle.to_string()),
+            data_file: args.value_of("data").map(|data_file| data_file.to_string()),
+
+            table_file: args.value_of("table").map(|table_file| table_file.to_string()),
+            name_column: args.value_of("name_column").map(|name_column| name_column.to_string()),
+
+            variables: args.values_of("var").map(|variables| variables.map(|variable| variable.to_string()).collect()).unwrap_or_default(),
+            variables_file: args.value_of("vars_file").map(|variables_file| variables_file.to_string()),
+
+            answers: args.values_of("answer").map(|answers| answers.map(|answer| answer.to_string()).collect()).unwrap_or_default(),
+            answers_file: args.value_of("answers_file").map(|answers_file| answers_file.to_string()),
+            no_prompt: args.is_present("no_prompt"),
 
             file_has_no_extension: extension_list.len() == 1,
 
             shove_header: if header_file == "" { None } else { Some(header_file.to_string()) }
         }
     } 
-}
\ No newline at end of file
+}
+
+#[test]
+fn combined_enumeration_flags_enable_each_axis() {
+    let to_args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+
+    let args = ProgramArguments::from_args(to_args(&["tt", "-f", "model.h", "-n", "-pl"]));
+    assert_eq!(vec![PLATFORM_AXIS.to_string(), LANGUAGE_AXIS.to_string()], args.enumeration_axes);
+    assert_eq!("model.h", args.file_name);
+
+    let args = ProgramArguments::from_args(to_args(&["tt", "-f", "model.h", "-l", "--platforms", "linux,!windows"]));
+    assert_eq!(vec![format!("{:}=linux,!windows", PLATFORM_AXIS), LANGUAGE_AXIS.to_string()], args.enumeration_axes);
+}
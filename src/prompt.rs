@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::io::{BufRead, IsTerminal, Write};
+
+use log::*;
+
+// Answers the questions asked by PROMPT. Each question is only asked once per run, so every
+//  file that gets enumerated reuses the same answer. Answers can be given ahead of time with
+//  --answer and --answers_file, which is the only place answers come from when tt isn't
+//  attached to a terminal.
+#[derive(Debug)]
+pub struct Prompter {
+    answers: HashMap<String, String>,
+    interactive: bool
+}
+
+impl Prompter {
+    pub fn new(preset_answers: HashMap<String, String>, allow_interactive: bool) -> Self {
+        Self {
+            answers: preset_answers,
+            interactive: allow_interactive && std::io::stdin().is_terminal()
+        }
+    }
+
+    pub fn non_interactive(preset_answers: HashMap<String, String>) -> Self {
+        Self {
+            answers: preset_answers,
+            interactive: false
+        }
+    }
+
+    pub fn ask(&mut self, question: &str, default: Option<&str>, choices: &[String]) -> Result<String, String> {
+        let question = question.trim();
+
+        if let Some(answer) = self.answers.get(question) {
+            return validate_choice(question, answer, choices);
+        }
+
+        let answer =
+            if self.interactive { self.ask_user(question, default, choices)? }
+            else                { default_answer(question, default)? };
+
+        info!("Answered prompt {{{:}}} with {{{:}}}", question, answer);
+
+        self.answers.insert(question.to_string(), answer.clone());
+        Ok(answer)
+    }
+
+    fn ask_user(&mut self, question: &str, default: Option<&str>, choices: &[String]) -> Result<String, String> {
+        let stdin = std::io::stdin();
+
+        loop {
+            // Prompts go to stderr so that output printed with -d stays clean.
+            let mut prompt_text = question.to_string();
+            if !choices.is_empty() {
+                let numbered_choices: Vec<String> = choices.iter().enumerate().map(|(index, choice)| format!("{:}) {:}", index + 1, choice)).collect();
+                prompt_text += &format!(" [{:}]", numbered_choices.join(", "));
+            }
+            if let Some(default) = default {
+                prompt_text += &format!(" ({:})", default);
+            }
+            eprint!("{:}: ", prompt_text);
+            let _ = std::io::stderr().flush();
+
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) | Err(_) => {
+                    // Nothing left to read, act as if we never had a terminal.
+                    self.interactive = false;
+                    return default_answer(question, default);
+                },
+                Ok(_) => {}
+            }
+
+            let line = line.trim();
+            if line.is_empty() {
+                match default {
+                    Some(default) => return Ok(default.to_string()),
+                    None => continue
+                }
+            }
+
+            // Choices can be picked by their number too.
+            let answer = match line.parse::<usize>() {
+                Ok(number) if number >= 1 && number <= choices.len() => choices[number - 1].clone(),
+                _ => line.to_string()
+            };
+
+            match validate_choice(question, &answer, choices) {
+                Ok(answer) => return Ok(answer),
+                Err(message) => eprintln!("{:}", message)
+            }
+        }
+    }
+}
+
+fn default_answer(question: &str, default: Option<&str>) -> Result<String, String> {
+    match default {
+        Some(default) => Ok(default.to_string()),
+        None => Err(format!("No answer was given for \"{:}\" and it has no default. Pass one with --answer \"{:}=value\" or --answers_file. ", question, question))
+    }
+}
+
+fn validate_choice(question: &str, answer: &str, choices: &[String]) -> Result<String, String> {
+    let answer = answer.trim();
+
+    if choices.is_empty() || choices.iter().any(|choice| choice == answer) {
+        Ok(answer.to_string())
+    } else {
+        Err(format!("{:} is not one of the choices for \"{:}\", pick one of {:}. ", answer, question, choices.join(", ")))
+    }
+}
+
+// Answers from the command line are written as question=answer.
+pub fn parse_answer(text: &str) -> Result<(String, String), String> {
+    match text.split_once('=') {
+        Some((question, answer)) if !question.trim().is_empty() => Ok((question.trim().to_string(), answer.trim().to_string())),
+        _ => Err(format!("Answers are written as \"question=answer\", got {:}. ", text))
+    }
+}
+
+// An answers file is a JSON object of question to answer.
+pub fn load_answers_file(path: &str) -> Result<HashMap<String, String>, String> {
+    let raw_answers = read_to_string(path).map_err(|e| format!("Failed to load answers file {:}. {:}", path, e))?;
+
+    serde_json::from_str(&raw_answers).map_err(|e| format!("Answers file {:} should be a JSON object of question to answer. {:}", path, e))
+}
+
+#[test]
+fn preset_answers_are_used_and_checked_against_choices() {
+    let mut preset_answers = HashMap::new();
+    preset_answers.insert("License".to_string(), "GPL".to_string());
+
+    let mut prompter = Prompter::non_interactive(preset_answers);
+    let choices = vec!["MIT".to_string(), "GPL".to_string()];
+
+    assert_eq!(Ok("GPL".to_string()), prompter.ask("License", Some("MIT"), &choices));
+    assert!(prompter.ask("License", None, &["MIT".to_string()]).is_err());
+}
+
+#[test]
+fn unanswered_prompts_fall_back_to_default() {
+    let mut prompter = Prompter::non_interactive(HashMap::new());
+
+    assert_eq!(Ok("QObject".to_string()), prompter.ask("Base class", Some("QObject"), &Vec::new()));
+    assert!(prompter.ask("Description", None, &Vec::new()).is_err());
+}
+
+#[test]
+fn answers_are_parsed_from_text() {
+    assert_eq!(Ok(("Base class".to_string(), "QWidget".to_string())), parse_answer("Base class = QWidget"));
+    assert!(parse_answer("QWidget").is_err());
+}
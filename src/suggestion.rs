@@ -0,0 +1,73 @@
+// Finds the closest match to a misspelled name, this is what powers the "Did you mean?" part of errors.
+
+// Number of single character insertions, deletions or substitutions it takes to turn one string into the other.
+pub fn edit_distance(from: &str, to: &str) -> usize {
+    let from: Vec<char> = from.chars().collect();
+    let to: Vec<char> = to.chars().collect();
+
+    // Only the previous row of the table is needed to work out the current one.
+    let mut previous_row: Vec<usize> = (0..=to.len()).collect();
+    let mut current_row: Vec<usize> = vec![0; to.len() + 1];
+
+    for (from_index, from_character) in from.iter().enumerate() {
+        current_row[0] = from_index + 1;
+
+        for (to_index, to_character) in to.iter().enumerate() {
+            let substitution_cost = if from_character == to_character { 0 } else { 1 };
+
+            current_row[to_index + 1] = (previous_row[to_index] + substitution_cost)
+                .min(previous_row[to_index + 1] + 1)
+                .min(current_row[to_index] + 1);
+        }
+
+        std::mem::swap(&mut previous_row, &mut current_row);
+    }
+
+    previous_row[to.len()]
+}
+
+// Gives back the candidate that is closest to word, as long as it's close enough to be a likely typo.
+//  Case is ignored when comparing so that things like "file_name" still find "FILE_NAME".
+pub fn did_you_mean<S: AsRef<str>>(word: &str, candidates: &[S]) -> Option<String> {
+    let word = word.trim();
+    if word.is_empty() {
+        return None;
+    }
+
+    let lowercase_word = word.to_lowercase();
+    let max_distance = (word.chars().count() / 3).max(2);
+
+    candidates
+        .iter()
+        .map(|candidate| candidate.as_ref())
+        .filter(|candidate| *candidate != word)
+        .map(|candidate| (candidate, edit_distance(&lowercase_word, &candidate.to_lowercase())))
+        .filter(|(_, distance)| *distance <= max_distance && *distance < word.chars().count())
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(candidate, _)| candidate.to_string())
+}
+
+#[test]
+fn edit_distance_counts_changes() {
+    assert_eq!(0, edit_distance("kabob", "kabob"));
+    assert_eq!(2, edit_distance("kebab", "kabob"));
+    assert_eq!(1, edit_distance("FILE_NAM", "FILE_NAME"));
+    assert_eq!(3, edit_distance("", "abc"));
+}
+
+#[test]
+fn did_you_mean_finds_closest_candidate() {
+    let formatters = ["caps", "lower", "spaced", "pascal", "camel", "kabob"];
+
+    assert_eq!(Some("kabob".to_string()), did_you_mean("kebab", &formatters));
+    assert_eq!(Some("camel".to_string()), did_you_mean("camle", &formatters));
+    assert_eq!(None, did_you_mean("snake", &formatters));
+}
+
+#[test]
+fn did_you_mean_ignores_case() {
+    let tokens = ["FILE_NAME", "FILE_NAME_AS_TYPE", "PLATFORM"];
+
+    assert_eq!(Some("FILE_NAME".to_string()), did_you_mean("file_name", &tokens));
+    assert_eq!(None, did_you_mean("FILE_NAME", &tokens));
+}
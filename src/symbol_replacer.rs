@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use log::*;
+use serde_json::Value;
 
 use crate::template_file_list::TemplateFile;
 use crate::file_manip::{get_current_path, get_current_dir_name};
@@ -9,253 +10,912 @@ use crate::formatter::*;
 use crate::file_harvester::*;
 use crate::token::*;
 use crate::parser::*;
-use crate::config::Config;
+use crate::config::{Config, EnumerationAxis};
 use crate::file_context::*;
+use crate::condition::*;
+use crate::template_scope::*;
+use crate::diagnostic::*;
+use crate::suggestion::did_you_mean;
+use crate::prompt::Prompter;
+use crate::data::*;
 
 use crate::platform_specific::*;
 
-pub fn replace_symbols(
-    unprocessed_file: &TemplateFile, 
-    file_context: &FileContext, 
-    harvest_location: &Option<String>, 
-    config: &Config) -> String {
+// Every token tt knows about, used to suggest what a misspelled token was meant to be.
+pub const KNOWN_TOKENS: &[&str] = &[
+    "BANNER", "BLOCK", "CALL", "CURRENT_DATE", "CURRENT_TIME", "DATA", "DEFINE_TEMPLATE_VAR", "DEVICE_NAME", "DIR", "DIR_AS_TYPE",
+    "EACH_FILE_IN_DIR", "ENUMERATION", "ENV", "EXTENDS", "EXTENSION", "FILE_NAME", "FILE_NAME_AS_TYPE", "FILE_NAME_WITHOUT_EXTENSION",
+    "FORCE_FILE_NAME", "FOR_EACH_FILE_IN_DIR", "FOR_EACH_ITEM", "HARVEST_EACH_SUBDIR", "HARVEST_SUBDIR", "IF", "IMPORT", "INDEX",
+    "LANGUAGE", "NAMING_PATTERN", "NUMBER", "OS", "PARENT_DIR", "PARTNER_FILE", "PATH", "PLATFORM", "PROMPT", "PWD", "RELATIVE_IMPORT",
+    "REPEAT_X_TIMES", "TEMPLATE_VAR", "THIS_FILES_EXTENSION", "THIS_FILES_NAME", "THIS_FILES_NAME_AS_TYPE",
+    "THIS_FILES_PATH", "TRAVERSE_UP_TO_DIR", "USER", "USER_VAR", "VERSION"
+];
+
+// How many files deep IMPORT, RELATIVE_IMPORT and CALL can go before giving up.
+const MAX_TEMPLATE_DEPTH: usize = 32;
+
+// The most times REPEAT_X_TIMES repeats a line, so that a mistyped count doesn't try to build a huge file.
+const MAX_REPEAT_COUNT: usize = 10000;
+
+// Added to an enumeration axis' token to read from the table of its value, ie: PLATFORM_VALUE
+const ENUMERATION_TABLE_SUFFIX: &str = "_VALUE";
+
+// The directory in the templates directory that CALL loads partials from, it isn't a bundle.
+pub const PARTIAL_DIRECTORY: &str = "partials";
+
+const NO_DATA_FILE: &str = "DATA and FOR_EACH_ITEM need a data file, pass one with --data. ";
+
+// Everything that changes as a template gets evaluated. Sources is a stack of the templates
+//  being evaluated, an IMPORT pushes the imported file on top so that anything it reports
+//  points into the right file. The prompter and data are shared between every file generated in a run.
+pub struct EvaluationState<'a> {
+    pub scope: TemplateScope,
+    pub diagnostics: Vec<Diagnostic>,
+    sources: Vec<TemplateSource>,
+    prompter: Option<&'a mut Prompter>,
+    data: Option<&'a Value>
+}
+
+struct TemplateSource {
+    path: String,
+    text: String
+}
 
-    let possible_matches = Parser::find_first_token(&unprocessed_file.template_file_data);
-    if possible_matches.is_none() {
-        // Nothing to do. 
-        return unprocessed_file.template_file_data.clone(); 
+impl<'a> EvaluationState<'a> {
+    pub fn new() -> Self {
+        Self {
+            scope: TemplateScope::new(),
+            diagnostics: Vec::new(),
+            sources: Vec::new(),
+            prompter: None,
+            data: None
+        }
     }
 
-    let processed_template = unprocessed_file.template_file_data.clone();
-    replace_sub_symbols(
-        &processed_template, 
-        file_context, 
-        None,
-        harvest_location, 
-        config)
+    pub fn with_prompter(prompter: &'a mut Prompter) -> Self {
+        Self {
+            prompter: Some(prompter),
+            ..Self::new()
+        }
+    }
+
+    // The values from --data that DATA and FOR_EACH_ITEM read from.
+    pub fn with_data(self, data: Option<&'a Value>) -> Self {
+        Self {
+            data,
+            ..self
+        }
+    }
+
+    pub fn push_source(&mut self, path: &str, text: &str) {
+        self.sources.push(TemplateSource {
+            path: path.to_string(),
+            text: text.to_string()
+        });
+    }
+
+    pub fn pop_source(&mut self) {
+        self.sources.pop();
+    }
+
+    // The file that is currently being evaluated, relative imports are relative to it.
+    pub fn current_path(&self) -> Option<&str> {
+        self.sources.last().map(|source| source.path.as_ref())
+    }
+
+    // Checks that the file at path can be evaluated from where we are. A file that is already being
+    //  evaluated further up would end up evaluating itself forever.
+    pub fn can_include(&self, path: &str) -> Result<(), String> {
+        let path_to_include = canonical_path(path);
+        if self.sources.iter().any(|source| canonical_path(&source.path) == path_to_include) {
+            let chain: Vec<&str> = self.sources.iter().map(|source| source.path.as_ref()).chain(std::iter::once(path)).collect();
+            return Err(format!("Import cycle found, {:} ends up including itself: {:}", path, chain.join(" -> ")));
+        }
+
+        if self.sources.len() >= MAX_TEMPLATE_DEPTH {
+            return Err(format!("Imports are nested more than {:} files deep, stopping at {:}. ", MAX_TEMPLATE_DEPTH, path));
+        }
+
+        Ok(())
+    }
+
+    pub fn report(&mut self, token_node: &TokenNode, error: &TokenError, evaluated_token: Option<&Token>) {
+
+        // Point at the argument that's to blame if we know which one it is.
+        let (span, argument) = match error.argument {
+            Some(index) if index < token_node.parameter_positions.len() => {
+                let argument_value = match evaluated_token {
+                    Some(token) if token.variables.as_ref().is_some_and(|variables| index < variables.len()) =>
+                        format!("argument {:} {{{:}}}", index + 1, token.get_variable_as_string(index).trim()),
+                    _ => format!("argument {:}", index + 1)
+                };
+                (argument_span(token_node, index), Some(argument_value))
+            },
+            _ => (token_node.span, None)
+        };
+
+        self.add_diagnostic(span, &token_node.id, argument, &error.message, error.suggestion.clone());
+    }
+
+    pub fn report_invalid(&mut self, invalid_token: &InvalidToken) {
+        let suggestion = match &invalid_token.error {
+            TokenParseError::InvalidIdentifier(identifier) => did_you_mean(identifier, KNOWN_TOKENS),
+            _ => None
+        };
+
+        self.add_diagnostic(invalid_token.span, &invalid_token.text, None, &invalid_token.error.to_string(), suggestion);
+    }
+
+    fn add_diagnostic(&mut self, span: Span, token: &str, argument: Option<String>, message: &str, suggestion: Option<String>) {
+        let diagnostic = match self.sources.last() {
+            Some(source) => Diagnostic::new(&source.path, &source.text, span, token, argument, message),
+            None         => Diagnostic::new("", "", span, token, argument, message)
+        }.with_suggestion(suggestion);
+
+        // Loops can hit the same problem once per iteration, once is enough to tell the user.
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
 }
 
-pub fn replace_sub_symbols(
-    data_to_replace: &String, 
-    file_context: &FileContext, 
-    parent_context: Option<&FileContext>,
-    harvest_location: &Option<String>, 
-    config: &Config) -> String {
+// The same file can be reached through different paths, ie: "templates/a.h" and "templates/partials/../a.h"
+fn canonical_path(path: &str) -> String {
+    match std::fs::canonicalize(path) {
+        Ok(canonical_path) => canonical_path.to_string_lossy().to_string(),
+        Err(_) => path.to_string()
+    }
+}
 
-    let mut processed_template = data_to_replace.clone();
-    let mut token = Parser::find_first_token(&processed_template);
+// The span of a single parameter. Parameters are seperated by "|||" and the last one
+//  is followed by "}[]", which is always on the same line as where the next thing starts.
+fn argument_span(token_node: &TokenNode, index: usize) -> Span {
+    let end = match token_node.parameter_positions.get(index + 1) {
+        Some(next_position) => *next_position,
+        None => token_node.span.end
+    };
 
-    while token.is_some() {
-        let found_token = token.unwrap();
+    Span {
+        start: token_node.parameter_positions[index],
+        end: Position {
+            line: end.line,
+            column: end.column.saturating_sub(3).max(1)
+        }
+    }
+}
 
-        let template_start: String = String::from(&processed_template[..found_token.start]);
-        let template_end:   String = String::from(&processed_template[found_token.end..]);
+// A template evaluated for a single output file, along with everything that went wrong while evaluating it.
+pub struct EvaluatedTemplate {
+    pub text: String,
+    pub diagnostics: Vec<Diagnostic>,
 
-        let replacement_symbol = create_replacement_value(
-            &processed_template[found_token.start..found_token.end], 
-            file_context, 
-            parent_context, 
-            harvest_location, 
-            &config);
+    // Template variables defined at the top level of the template, outside of any loop. 
+    pub variables: HashMap<String, String>
+}
 
-        processed_template = template_start + &replacement_symbol + &template_end;
+impl EvaluatedTemplate {
+    pub fn has_errors(&self) -> bool {
+        !self.diagnostics.is_empty()
+    }
+}
 
-        token = Parser::find_first_token(&processed_template);
+pub fn replace_symbols(
+    unprocessed_file: &TemplateFile,
+    file_context: &FileContext,
+    harvest_location: &Option<String>,
+    data: Option<&Value>,
+    config: &Config,
+    prompter: &mut Prompter) -> EvaluatedTemplate {
+
+    let mut state = EvaluationState::with_prompter(prompter).with_data(data);
+    state.push_source(&unprocessed_file.template_file_path, &unprocessed_file.template_file_data);
+
+    // Variables that come with the file, like the columns of its --table row, can be used anywhere in the template.
+    for (name, value) in &file_context.variables {
+        state.scope.define(name, value);
     }
 
+    let processed_template = evaluate_nodes(
+        &unprocessed_file.template_tree,
+        file_context,
+        None,
+        harvest_location,
+        config,
+        &mut state);
+
+    for diagnostic in &state.diagnostics {
+        error!("{:}", diagnostic);
+    }
+
+    EvaluatedTemplate {
+        text: processed_template,
+        variables: state.scope.root_variables(),
+        diagnostics: state.diagnostics
+    }
+}
+
+// Evaluates text that isn't part of a template file, like the name of a file in a template bundle. 
+pub fn replace_symbols_in_text(
+    source_path: &str,
+    text: &str,
+    file_context: &FileContext,
+    data: Option<&Value>,
+    config: &Config,
+    prompter: &mut Prompter) -> EvaluatedTemplate {
+
+    let mut state = EvaluationState::with_prompter(prompter).with_data(data);
+    for (name, value) in &file_context.variables {
+        state.scope.define(name, value);
+    }
+
+    let processed_text = replace_sub_symbols(source_path, text, file_context, None, &None, config, &mut state);
+
+    for diagnostic in &state.diagnostics {
+        error!("{:}", diagnostic);
+    }
+
+    EvaluatedTemplate {
+        text: processed_text,
+        variables: state.scope.root_variables(),
+        diagnostics: state.diagnostics
+    }
+}
+
+// Parses and evaluates text as a template. This is only for text that is supposed to be
+//  a template, like an IMPORTed file. Values that come out of tokens are never evaluated again.
+pub fn replace_sub_symbols(
+    source_path: &str,
+    data_to_replace: &str,
+    file_context: &FileContext,
+    parent_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> String {
+
+    state.push_source(source_path, data_to_replace);
+    let processed_template = evaluate_nodes(&Parser::parse(data_to_replace), file_context, parent_context, harvest_location, config, state);
+    state.pop_source();
+
     processed_template
+}
 
+pub fn evaluate_nodes(
+    nodes: &[TemplateNode],
+    file_context: &FileContext,
+    parent_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> String {
+
+    let mut processed_template = String::new();
+    for node in nodes {
+        match node {
+            TemplateNode::Text(text)  => processed_template += text,
+            TemplateNode::Token(token) => processed_template += &evaluate_token(token, file_context, parent_context, harvest_location, config, state),
+            TemplateNode::Invalid(invalid_token) => {
+                state.report_invalid(invalid_token);
+                processed_template += "ERR";
+            },
+            TemplateNode::Included(included) => {
+                state.push_source(&included.path, &included.text);
+                processed_template += &evaluate_nodes(&included.nodes, file_context, parent_context, harvest_location, config, state);
+                state.pop_source();
+            }
+        }
+    }
+
+    processed_template
 }
 
-pub fn create_replacement_value(
-    token_text: &str, 
-    file_context: &FileContext, 
+fn evaluate_token(
+    token_node: &TokenNode,
+    file_context: &FileContext,
     parent_file_context: Option<&FileContext>,
-    harvest_location: &Option<String>, 
-    config: &Config) -> String {
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> String {
 
-    info!("Matching against token: {:}", token_text);
+    info!("Evaluating token: {:}", token_node.id);
     info!("File Context: {:?}", file_context);
 
-    let token = Token::from_string(token_text);
-    if token.is_err() {
-        error!("Failed to parse token");
-        return "ERR".to_string()
+    // These tokens decide if and how many times their parameters get evaluated, the rest
+    //  get all of their parameters evaluated up front.
+    let mut evaluated_token: Option<Token> = None;
+    let replacement_value = match token_node.id.as_ref() {
+        "IF"                   => conditional(token_node, file_context, parent_file_context, harvest_location, config, state),
+        "FOR_EACH_FILE_IN_DIR" => for_each_file_in_dir(token_node, file_context, harvest_location, config, state),
+        "FOR_EACH_ITEM"        => for_each_item(token_node, file_context, parent_file_context, harvest_location, config, state),
+        "REPEAT_X_TIMES"       => repeat_x_times(token_node, file_context, parent_file_context, harvest_location, config, state),
+        "NAMING_PATTERN"       => Ok(String::new()), /* Only used to name the file, the pattern's tokens aren't evaluated. See template_file_list.rs */
+        _ => {
+            let token = evaluate_parameters(token_node, file_context, parent_file_context, harvest_location, config, state);
+            let replacement_value = create_replacement_value(&token, file_context, parent_file_context, harvest_location, config, state);
+            evaluated_token = Some(token);
+            replacement_value
+        }
+    };
+
+    // Filters work on whatever the token evaluated to, ie: []DIR|caps|+_H[]
+    let replacement_value = replacement_value.and_then(|value| apply_filters(&value, &token_node.filters));
+
+    match replacement_value {
+        Ok(value) => value,
+        Err(error) => {
+            state.report(token_node, &error, evaluated_token.as_ref());
+            "ERR".to_string()
+        }
+    }
+}
+
+fn evaluate_parameters(
+    token_node: &TokenNode,
+    file_context: &FileContext,
+    parent_file_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> Token {
+
+    let mut variables: Vec<TokenVariable> = Vec::new();
+    for parameter in &token_node.parameters {
+        variables.push(evaluate_parameter_list(parameter, file_context, parent_file_context, harvest_location, config, state));
+    }
+
+    Token {
+        id: token_node.id.clone(),
+        variables: if variables.is_empty() { None } else { Some(variables) }
+    }
+}
+
+// Only commas written in the template split a parameter into a list, a comma in a value that
+//  a token evaluated to stays part of that value. 
+fn evaluate_parameter_list(
+    parameter: &[TemplateNode],
+    file_context: &FileContext,
+    parent_file_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> TokenVariable {
+
+    let mut items: Vec<String> = vec![String::new()];
+    for node in parameter {
+        match node {
+            TemplateNode::Text(text) => {
+                let mut written_items = text.split(',');
+                if let (Some(last_item), Some(first_written_item)) = (items.last_mut(), written_items.next()) {
+                    *last_item += first_written_item;
+                }
+                items.extend(written_items.map(|item| item.to_string()));
+            },
+            _ => {
+                let value = evaluate_nodes(std::slice::from_ref(node), file_context, parent_file_context, harvest_location, config, state);
+                if let Some(last_item) = items.last_mut() {
+                    *last_item += &value;
+                }
+            }
+        }
     }
-    let token = token.unwrap();
+
+    TokenVariable { variable_list: items }
+}
+
+// Same as evaluate_parameters but for a single parameter, None if the token doesn't have that many.
+fn evaluate_parameter(
+    token_node: &TokenNode,
+    index: usize,
+    file_context: &FileContext,
+    parent_file_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> Option<String> {
+
+    let parameter = token_node.parameters.get(index)?;
+    Some(evaluate_nodes(parameter, file_context, parent_file_context, harvest_location, config, state))
+}
+
+fn create_replacement_value(
+    token: &Token,
+    file_context: &FileContext,
+    parent_file_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> Result<String, TokenError> {
+
     info!("Replacement token: {:?}", token);
 
-    let replacement_value: Option<String>;
-    if token.has_variables() {
-        replacement_value = match token.id.as_ref() {
-            "CURRENT_DATE"         => { Some(get_current_date(&token.get_variable_as_string(0))) },
-            "CURRENT_TIME"         => { Some(get_current_time(&token.get_variable_as_string(0))) },
-            "PARENT_DIR"           => { Some("UNIMPLEMENTED".to_string()) },
-            "EACH_FILE_IN_DIR"     => { Some(harvest_files_from_dir_as_string(harvest_location, &token.get_variable_at(0), harvest_location.is_some())) },
-            "FOR_EACH_FILE_IN_DIR" => { for_each_file_in_dir(&token, &file_context, harvest_location, &config) },
-            "REPEAT_X_TIMES"       => { Some("UNIMPLEMENTED".to_string()) }, 
-            "USER_VAR"             => { user_variable(&token.get_variable_as_string(0), &config.user_variables) }, 
-            "FILE_NAME_AS_TYPE"    => { file_name_as_type_with_args(&file_context.expand_with_enumerations(), &token) },
-            "THIS_FILES_NAME_AS_TYPE" => match parent_file_context { 
-                                        Some(context) => file_name_as_type_with_args(&context.expand_with_enumerations(), &token),
-                                        None => file_name_as_type_with_args(&file_context.expand_with_enumerations(), &token)
+    let replacement_value = if token.has_variables() {
+        match token.id.as_ref() {
+            "CURRENT_DATE"         => { Ok(get_current_date(&token.get_variable_as_string(0))) },
+            "CURRENT_TIME"         => { Ok(get_current_time(&token.get_variable_as_string(0))) },
+            "PARENT_DIR"           => { Ok("UNIMPLEMENTED".to_string()) },
+            "EACH_FILE_IN_DIR"     => { Ok(harvest_files_from_dir_as_string(harvest_location, &token.get_variable_at(0), harvest_location.is_some(), optional_variable(token, 1).as_deref())) },
+            "USER_VAR"             => { user_variable(&token.get_variable_as_string(0), &config.user_variables) },
+            "ENV"                  => { environment_variable(token) },
+            "PROMPT"               => { prompt(token, state) },
+            "DATA"                 => { find_data(&token.get_variable_as_string(0), state).map(value_as_string) },
+            "FILE_NAME_AS_TYPE"    => { file_name_as_type_with_args(&file_context.expand_with_enumerations(), token) },
+            "THIS_FILES_NAME_AS_TYPE" => match parent_file_context {
+                                        Some(context) => file_name_as_type_with_args(&context.expand_with_enumerations(), token),
+                                        None => file_name_as_type_with_args(&file_context.expand_with_enumerations(), token)
                                       },
-            "IMPORT"               => { import_file(&token) },
-            "RELATIVE_IMPORT"      => { Some("UNIMPLEMENTED".to_string()) },         
-            "BANNER"               => { create_banner(&token.get_variable_as_string(0), &token.get_variable_as_string(1), file_context, harvest_location, config) },
-            "FILE_NAME"            => { file_name_with_args(&file_context.expand_with_enumerations(), &token, &file_context.extension)},
-            "FILE_NAME_WITHOUT_EXTENSION" => { file_name_without_extension_with_args(&file_context.name.clone(), &token)}
-            "HARVEST_SUBDIR"       => { Some("UNIMPLEMENTED".to_string()) },
-            "HARVEST_EACH_SUBDIR"  => { Some("UNIMPLEMENTED".to_string()) },
-            "DEFINE_TEMPLATE_VAR"  => { Some("UNIMPLEMENTED".to_string()) },
-            "TEMPLATE_VAR"         => { Some("UNIMPLEMENTED".to_string()) },
-            "TRAVERSE_UP_TO_DIR"   => { Some("UNIMPLEMENTED".to_string()) },
-            "THIS_FILES_NAME"      => { this_files_name(&token, file_context, parent_file_context) }, 
-            "FORCE_FILE_NAME"      => { Some("".to_string())}, /* Just blank out this line, we dont care about it's value here. See template_file_list.rs */ 
-            "ERR"                  =>   None,
-            _                      =>   None,
+            "IMPORT"               => { import_file(&template_directory_path(&token.get_variable_as_string(0)), file_context, parent_file_context, harvest_location, config, state) },
+            "RELATIVE_IMPORT"      => { import_file(&relative_path(&token.get_variable_as_string(0), state), file_context, parent_file_context, harvest_location, config, state) },
+            "CALL"                 => { call_partial(token, file_context, parent_file_context, harvest_location, config, state) },
+            "BLOCK"                => { Ok(optional_variable(token, 1).unwrap_or_default()) }, /* Overridden blocks are swapped in before evaluation. See template_file_list.rs */
+            "EXTENDS"              => { Err(TokenError::new("EXTENDS can only be used at the top level of a template, outside of any other variable. ")) },
+            "BANNER"               => { Ok(create_banner(&token.get_variable_as_string(0), &token.get_variable_as_string(1))) },
+            "FILE_NAME"            => { file_name_with_args(&file_context.file_stem(), token, &file_context.extension)},
+            "FILE_NAME_WITHOUT_EXTENSION" => { file_name_without_extension_with_args(&file_context.name.clone(), token)}
+            "HARVEST_SUBDIR"       => { Ok("UNIMPLEMENTED".to_string()) },
+            "HARVEST_EACH_SUBDIR"  => { Ok("UNIMPLEMENTED".to_string()) },
+            "DEFINE_TEMPLATE_VAR"  => { define_template_variable(token, &mut state.scope) },
+            "TEMPLATE_VAR"         => { template_variable(&token.get_variable_as_string(0), &state.scope) },
+            "TRAVERSE_UP_TO_DIR"   => { Ok("UNIMPLEMENTED".to_string()) },
+            "THIS_FILES_NAME"      => { this_files_name(token, file_context, parent_file_context) },
+            "FORCE_FILE_NAME"      => { Ok("".to_string())}, /* Just blank out this line, we dont care about it's value here. See template_file_list.rs */
+            _                      => { enumeration_table_value(token, file_context, config) },
         }
     } else {
-        replacement_value = match token.id.as_ref() {
-            "FILE_NAME"           => { Some(file_context.name_with_extension()) }
-            "THIS_FILES_NAME"     => { this_files_name(&token, file_context, parent_file_context) }, 
-            "FILE_NAME_AS_TYPE"   => { Some(string_in_pascal_case(&file_context.expand_with_enumerations())) },
-            "FILE_NAME_WITHOUT_EXTENSION" => { Some(file_context.name.clone()) },
-            "THIS_FILES_PATH"     => match parent_file_context { 
-                                        Some(context) => Some(context.path.clone()),
-                                        None => Some(file_context.path.clone()) 
+        match token.id.as_ref() {
+            "FILE_NAME"           => { Ok(file_context.name_with_extension()) }
+            "THIS_FILES_NAME"     => { this_files_name(token, file_context, parent_file_context) },
+            "FILE_NAME_AS_TYPE"   => { Ok(string_in_pascal_case(&file_context.expand_with_enumerations())) },
+            "FILE_NAME_WITHOUT_EXTENSION" => { Ok(file_context.name.clone()) },
+            "THIS_FILES_PATH"     => match parent_file_context {
+                                        Some(context) => Ok(context.path.clone()),
+                                        None => Ok(file_context.path.clone())
                                      },
-            "THIS_FILES_EXTENSION"=> match parent_file_context { 
-                                        Some(context) => Some(context.extension.clone()),
-                                        None => Some(file_context.extension.clone()) 
+            "THIS_FILES_EXTENSION"=> match parent_file_context {
+                                        Some(context) => Ok(context.extension.clone()),
+                                        None => Ok(file_context.extension.clone())
                                      },
-            "THIS_FILES_NAME_AS_TYPE" => match parent_file_context { 
-                                        Some(context) => Some(string_in_pascal_case(&context.name)),
-                                        None => Some(string_in_pascal_case(&file_context.name ))
+            "THIS_FILES_NAME_AS_TYPE" => match parent_file_context {
+                                        Some(context) => Ok(string_in_pascal_case(&context.name)),
+                                        None => Ok(string_in_pascal_case(&file_context.name ))
                                      },
-            "PARTNER_FILE"        => { find_partner_file(&file_context, &config.partner_file_map) }, 
-            "EXTENSION"           => { Some(file_context.extension.clone()) },
-            "DIR"                 => { Some(get_current_dir_name().unwrap_or(String::new()))},
-            "DIR_AS_TYPE"         => { Some(string_in_pascal_case(&get_current_dir_name().unwrap_or(String::new()))) },
-            "PWD"                 => { Some(get_current_path().unwrap_or(String::new())) },
-            "PATH"                => { Some(".".to_string())},   
-            "CURRENT_DATE"        => { Some(get_current_date("%m-%d-%Y")) },
-            "CURRENT_TIME"        => { Some(get_current_time("%H:%M")) },
-            "PLATFORM"            => { Some(replace_if_not_none("[]PLATFORM[]",    &file_context.enumerations.platform))    },
-            "LANGUAGE"            => { Some(replace_if_not_none("[]LANGUAGE[]",    &file_context.enumerations.language))    },
-            "ENUMERATION"         => { Some(replace_if_not_none("[]ENUMERATION[]", &file_context.enumerations.user_defined)) },
-            "USER"                => { Some(whoami::username()) },
-            "OS"                  => { Some(whoami::distro()) },
-            "DEVICE_NAME"         => { Some(whoami::devicename()) },
-            "VERSION"             => { Some(env!("CARGO_PKG_VERSION").to_string()) },
-            _                     =>   None
-        };
+            "PARTNER_FILE"        => { find_partner_file(file_context, &config.partner_file_map) },
+            "EXTENSION"           => { Ok(file_context.extension.clone()) },
+            "DIR"                 => { Ok(get_current_dir_name().unwrap_or_default())},
+            "DIR_AS_TYPE"         => { Ok(string_in_pascal_case(&get_current_dir_name().unwrap_or_default())) },
+            "PWD"                 => { Ok(get_current_path().unwrap_or_default()) },
+            "PATH"                => { Ok(".".to_string())},
+            "CURRENT_DATE"        => { Ok(get_current_date("%m-%d-%Y")) },
+            "CURRENT_TIME"        => { Ok(get_current_time("%H:%M")) },
+            "USER"                => { Ok(whoami::username()) },
+            "OS"                  => { Ok(whoami::distro()) },
+            "DEVICE_NAME"         => { Ok(whoami::devicename()) },
+            "VERSION"             => { Ok(env!("CARGO_PKG_VERSION").to_string()) },
+            "INDEX"               => { loop_iteration(token, &state.scope).map(|iteration| iteration.index.to_string()) },
+            "NUMBER"              => { loop_iteration(token, &state.scope).map(|iteration| (iteration.index + 1).to_string()) },
+            "DATA"                => { find_data("", state).map(value_as_string) },
+            _                     => { enumeration_value(token, file_context, config) }
+        }
+    };
+
+    replacement_value
+}
+
+// Every enumeration axis has a token named after it, ie: []PLATFORM[] or []BOARD[]. Files that
+//  weren't enumerated over the axis keep the token as is.
+fn enumeration_value(token: &Token, file_context: &FileContext, config: &Config) -> Result<String, TokenError> {
+    match config.enumeration_axis_for_token(&token.id) {
+        Some(axis) => Ok(replace_if_not_none(&format!("[]{:}[]", token.id), &file_context.enumerations.get(&axis.name))),
+        None => Err(unknown_token(token, config))
+    }
+}
+
+// Values of an enumeration axis can have a table in the configuration file, which is read with the axis'
+//  token followed by _VALUE, ie: []PLATFORM_VALUE{export_macro}[]. Like the axis token, files that
+//  weren't enumerated over the axis keep the token as is.
+fn enumeration_table_value(token: &Token, file_context: &FileContext, config: &Config) -> Result<String, TokenError> {
+    let axis = match enumeration_table_axis(&token.id, config) {
+        Some(axis) => axis,
+        None => return Err(unknown_token(token, config))
+    };
+
+    let key = token.get_variable_as_string(0);
+    let value = match file_context.enumerations.get(&axis.name) {
+        Some(value) => value,
+        None => return Ok(format!("[]{:}{{{:}}}[]", token.id, key))
+    };
+
+    let enumeration_value = axis.find_value(&value);
+    match enumeration_value.and_then(|enumeration_value| enumeration_value.table_value(&key)) {
+        Some(table_value) => Ok(value_as_string(table_value)),
+        None => {
+            let keys = enumeration_value.map(|enumeration_value| enumeration_value.table_keys()).unwrap_or_default();
+            Err(TokenError::with_argument(&format!("The {:} value {:} has no {:} in its table in the configuration file. ", axis.name, value, key), 0)
+                .with_suggestion(did_you_mean(&key, &keys)))
+        }
     }
+}
+
+fn enumeration_table_axis(token_id: &str, config: &Config) -> Option<EnumerationAxis> {
+    token_id.strip_suffix(ENUMERATION_TABLE_SUFFIX).and_then(|axis_token_id| config.enumeration_axis_for_token(axis_token_id))
+}
 
-    if replacement_value.is_none() {
-        error!(
-            "No match for token {:?}, putting 'ERR' in it's place.", 
-            token);
-        "ERR".to_string()
+fn unknown_token(token: &Token, config: &Config) -> TokenError {
+    let error = if token.has_variables() {
+        TokenError::new(&format!("{:} is not a token that takes parameters. ", token.id))
     } else {
-        replacement_value.unwrap()
+        TokenError::new(&format!("{:} is not a known token, or it needs parameters. ", token.id))
+    };
+
+    let mut token_names: Vec<String> = KNOWN_TOKENS.iter().map(|token_name| token_name.to_string()).collect();
+    token_names.extend(config.enumeration_axes().iter().map(|axis| axis.token_id()));
+    token_names.extend(config.enumeration_axes().iter().map(|axis| axis.token_id() + ENUMERATION_TABLE_SUFFIX));
+
+    error.with_suggestion(did_you_mean(&token.id, &token_names))
+}
+
+fn apply_filters(value: &str, filters: &[String]) -> Result<String, TokenError> {
+    if filters.is_empty() {
+        return Ok(value.to_string());
     }
 
-    // @Future: Implement a Did you mean? feature. 
+    format_append_and_remove(value, filters)
+        .map_err(|error| TokenError::new(&error.message).with_suggestion(error.suggestion))
 }
 
-fn for_each_file_in_dir(token: &Token, file_context: &FileContext, harvest_location: &Option<String>, config: &Config) -> Option<String>{
+// Formatting is always done with the first parameter of a token.
+fn format_error(error: FormatError) -> TokenError {
+    TokenError::with_argument(&error.message, 0).with_suggestion(error.suggestion)
+}
 
-    let token = token.clone();
+fn for_each_file_in_dir(
+    token_node: &TokenNode,
+    file_context: &FileContext,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> Result<String, TokenError> {
 
-    let variable_list = token.get_variable_at(0);
-    let mut include_list: Vec<String> = Vec::new();
-    for item in variable_list {
-        include_list.push(item.trim().to_string());
+    if token_node.parameters.len() < 2 {
+        return Err(TokenError::new("FOR_EACH_FILE_IN_DIR needs an include list and a line. ie: []FOR_EACH_FILE_IN_DIR{h, cpp|||line}[]"));
     }
 
-    let user_line = token.variables.unwrap()[1].rebuild_string();
+    // The include list and separator are evaluated once for the file being generated, the line gets evaluated once per harvested file.
+    let include_list = evaluate_parameter_list(&token_node.parameters[0], file_context, None, harvest_location, config, state).variable_list
+        .iter()
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect();
+    let user_line = &token_node.parameters[1];
+    let separator = evaluate_parameter(token_node, 2, file_context, None, harvest_location, config, state).unwrap_or_default();
 
     let harvested_files = harvest_files_from_dir(harvest_location, &include_list);
+    let count = harvested_files.len();
 
     let mut replacement_value: String = String::new();
-    for file in harvested_files {
+    for (index, file) in harvested_files.iter().enumerate() {
+        if index != 0 {
+            replacement_value += &separator;
+        }
+
         let mut harvest_file_context = FileContext::blank();
 
         harvest_file_context.name = replace_if_not_none("", &file.file_name);
         harvest_file_context.path = replace_if_not_none("", &file.path);
         harvest_file_context.extension = replace_if_not_none("", &file.extension);
 
-        replacement_value += &(replace_harvest_variables(&user_line, &harvest_file_context, file_context, harvest_location, config));
+        // Each pass over the line gets it's own frame so that template variables defined in the line don't leak into the next file.
+        state.scope.push_iteration_frame(LoopIteration { index, count });
+        replacement_value += &evaluate_nodes(user_line, &harvest_file_context, Some(file_context), harvest_location, config, state);
+        state.scope.pop_frame();
     }
 
     info!("--- Replacement Value: {:?} --- ", replacement_value);
 
-    Some(replacement_value)
+    Ok(replacement_value)
+}
+
+fn for_each_item(
+    token_node: &TokenNode,
+    file_context: &FileContext,
+    parent_file_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> Result<String, TokenError> {
+
+    if token_node.parameters.len() < 2 {
+        return Err(TokenError::new("FOR_EACH_ITEM needs the path of an array and a line. ie: []FOR_EACH_ITEM{fields|||line}[]"));
+    }
+
+    // The path and separator are evaluated once, the line gets evaluated once per item.
+    let path = evaluate_parameter(token_node, 0, file_context, parent_file_context, harvest_location, config, state).unwrap_or_default();
+    let items = match find_data(&path, state)? {
+        Value::Array(items) => items.clone(),
+        _ => return Err(TokenError::with_argument(&format!("{:} is not an array. ", path.trim()), 0))
+    };
+    let line = &token_node.parameters[1];
+    let separator = evaluate_parameter(token_node, 2, file_context, parent_file_context, harvest_location, config, state).unwrap_or_default();
+
+    let count = items.len();
+
+    let mut replacement_value = String::new();
+    for (index, item) in items.into_iter().enumerate() {
+        if index != 0 {
+            replacement_value += &separator;
+        }
+
+        state.scope.push_item_frame(LoopIteration { index, count }, item);
+        replacement_value += &evaluate_nodes(line, file_context, parent_file_context, harvest_location, config, state);
+        state.scope.pop_frame();
+    }
+
+    Ok(replacement_value)
+}
+
+// Paths are looked up on the item of the inner most FOR_EACH_ITEM, or on the whole data file outside of one.
+//  A path starting with $ is always looked up on the whole data file, ie: []DATA{$.name}[]
+fn find_data<'s>(path: &str, state: &'s EvaluationState) -> Result<&'s Value, TokenError> {
+    let path = path.trim();
+    let (data, path) = match path.strip_prefix('$') {
+        Some(path_from_root) => (state.data, path_from_root),
+        None => (state.scope.current_item().or(state.data), path)
+    };
+
+    match data {
+        Some(data) => find_value(data, path),
+        None => Err(TokenError::new(NO_DATA_FILE))
+    }
+}
+
+// Gets a parameter that doesn't have to be there, ie: the separator on EACH_FILE_IN_DIR
+fn optional_variable(token: &Token, index: usize) -> Option<String> {
+    match &token.variables {
+        Some(variables) if variables.len() > index => Some(variables[index].rebuild_string()),
+        _ => None
+    }
+}
+
+fn conditional(
+    token_node: &TokenNode,
+    file_context: &FileContext,
+    parent_file_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> Result<String, TokenError> {
+
+    let branches = &token_node.parameters;
+    if branches.len() < 2 {
+        return Err(TokenError::new("IF token requires a condition and at least one branch. ie: []IF{PLATFORM == windows|||text}[]"));
+    }
+    if branches.len() > 3 {
+        warn!("IF token has more than two branches, everything after the ELSE branch is ignored. ");
+    }
+
+    let condition = match evaluate_condition(&branches[0], file_context, parent_file_context, harvest_location, config, state) {
+        Ok(condition) => condition,
+        Err(e) => {
+            return Err(TokenError::with_argument(&format!("Failed to parse IF condition. {:}", e), 0));
+        }
+    };
+
+    let subject_value = condition_subject_value(&condition.subject, file_context, parent_file_context, config, state)
+        .map_err(|message| TokenError::with_argument(&message, 0))?;
+
+    // Only the branch that is chosen gets evaluated.
+    let chosen_branch =
+        if condition.evaluate(&subject_value) { 1 }
+        else                                 { 2 };
+
+    Ok(evaluate_parameter(token_node, chosen_branch, file_context, parent_file_context, harvest_location, config, state).unwrap_or_default())
+}
+
+// Tokens in a condition are evaluated after it's parsed, so a value with == or a comma in it is compared
+//  as is instead of changing the condition. Each token is held by a placeholder until then. 
+fn evaluate_condition(
+    condition_nodes: &[TemplateNode],
+    file_context: &FileContext,
+    parent_file_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> Result<Condition, String> {
+
+    let mut condition_text = String::new();
+    let mut values: Vec<String> = Vec::new();
+    for node in condition_nodes {
+        match node {
+            TemplateNode::Text(text) => condition_text += text,
+            _ => {
+                condition_text += &condition_placeholder(values.len());
+                values.push(evaluate_nodes(std::slice::from_ref(node), file_context, parent_file_context, harvest_location, config, state));
+            }
+        }
+    }
+
+    let condition = Condition::from_string(&condition_text)?;
+    Ok(condition.map_operands(|operand| {
+        values.iter().enumerate().fold(operand.to_string(), |operand, (index, value)| operand.replace(&condition_placeholder(index), value))
+    }))
+}
+
+fn condition_placeholder(index: usize) -> String {
+    format!("\u{1}{:}\u{1}", index)
+}
+
+// Checks a condition written the same as the one in an IF for a file, without it being part of a template. 
+pub fn check_condition(condition_text: &str, file_context: &FileContext, data: Option<&Value>, config: &Config) -> Result<bool, String> {
+    let condition = Condition::from_string(condition_text)?;
+    let state = EvaluationState::new().with_data(data);
+
+    let subject_value = condition_subject_value(&condition.subject, file_context, None, config, &state)?;
+
+    Ok(condition.evaluate(&subject_value))
+}
+
+// Gives back an error if the subject isn't something that can be used in a condition, and
+//  Ok(None) if the subject is valid but has no value for this file.
+fn condition_subject_value(
+    subject: &ConditionSubject,
+    file_context: &FileContext,
+    parent_file_context: Option<&FileContext>,
+    config: &Config,
+    state: &EvaluationState) -> Result<Option<String>, String> {
+
+    let scope = &state.scope;
+
+    // Harvested files never have enumerations, so inside of FOR_EACH_FILE_IN_DIR these come from the file being generated.
+    let enumerations = &parent_file_context.unwrap_or(file_context).enumerations;
+
+    if (subject.id == "IS_FIRST" || subject.id == "IS_LAST") && scope.current_iteration().is_none() {
+        return Err(format!("{:} can only be used inside of REPEAT_X_TIMES, FOR_EACH_FILE_IN_DIR or FOR_EACH_ITEM. ", subject.id));
+    }
+
+    match subject.id.as_ref() {
+        "EXTENSION"   => Ok(if file_context.extension.is_empty() { None } else { Some(file_context.extension.clone()) }),
+        "USER_VAR"    => match &subject.argument {
+                            Some(name) => Ok(config.user_variables.get(name).cloned()),
+                            None => Err("USER_VAR in an IF condition needs a variable name. ie: []IF{USER_VAR{TEAM}|||text}[]".to_string())
+                         },
+        "ENV"         => match &subject.argument {
+                            Some(name) => Ok(std::env::var(name).ok()),
+                            None => Err("ENV in an IF condition needs a variable name. ie: []IF{ENV{CI}|||text}[]".to_string())
+                         },
+        "IS_FIRST"    => Ok(scope.current_iteration().and_then(|iteration| if iteration.index == 0 { Some("true".to_string()) } else { None })),
+        "IS_LAST"     => Ok(scope.current_iteration().and_then(|iteration| if iteration.index + 1 == iteration.count { Some("true".to_string()) } else { None })),
+        "DATA"        => match &subject.argument {
+                            // A missing field is the same as one that isn't set, so optional fields can be checked for.
+                            Some(path) if state.data.is_some() => Ok(find_data(path, state).ok().and_then(value_as_condition)),
+                            Some(_) => Err(NO_DATA_FILE.to_string()),
+                            None => Err("DATA in an IF condition needs a path. ie: []IF{DATA{fields.0.optional}|||text}[]".to_string())
+                         },
+        "TEMPLATE_VAR" => match &subject.argument {
+                            Some(name) => Ok(scope.lookup(name)),
+                            None => Err("TEMPLATE_VAR in an IF condition needs a variable name. ie: []IF{TEMPLATE_VAR{NAME}|||text}[]".to_string())
+                         },
+        _ => match (config.enumeration_axis_for_token(&subject.id), enumeration_table_axis(&subject.id, config)) {
+            (Some(axis), _) => Ok(enumerations.get(&axis.name)),
+            (None, Some(axis)) => match &subject.argument {
+                Some(key) => Ok(enumerations
+                    .get(&axis.name)
+                    .and_then(|value| axis.find_value(&value).and_then(|enumeration_value| enumeration_value.table_value(key)).and_then(value_as_condition))),
+                None => Err(format!("{:} in an IF condition needs a key. ie: []IF{{{:}{{export_macro}}|||text}}[]", subject.id, subject.id))
+            },
+            (None, None) => Err(format!("{:} cannot be used in an IF condition. ", subject.id))
+        }
+    }
+}
+
+fn repeat_x_times(
+    token_node: &TokenNode,
+    file_context: &FileContext,
+    parent_file_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> Result<String, TokenError> {
+
+    if token_node.parameters.len() != 2 {
+        return Err(TokenError::new("REPEAT_X_TIMES needs a count and a line to repeat. ie: []REPEAT_X_TIMES{3|||line}[]"));
+    }
+
+    // The count can itself be made of other variables, ie: []REPEAT_X_TIMES{[]USER_VAR{COUNT}[]|||line}[]
+    let count_text = evaluate_parameter(token_node, 0, file_context, parent_file_context, harvest_location, config, state).unwrap_or_default();
+    let count = repeat_count(count_text.trim(), config, &state.scope).map_err(|message| TokenError::with_argument(&message, 0))?;
+    if count > MAX_REPEAT_COUNT {
+        return Err(TokenError::with_argument(&format!("REPEAT_X_TIMES can repeat a line at most {:} times, the count is {:}. ", MAX_REPEAT_COUNT, count), 0));
+    }
+
+    let line = &token_node.parameters[1];
+
+    let mut replacement_value = String::new();
+    for index in 0..count {
+        state.scope.push_iteration_frame(LoopIteration { index, count });
+        replacement_value += &evaluate_nodes(line, file_context, parent_file_context, harvest_location, config, state);
+        state.scope.pop_frame();
+    }
+
+    Ok(replacement_value)
+}
+
+// A count is either a number or the name of a variable holding a number.
+fn repeat_count(count_text: &str, config: &Config, scope: &TemplateScope) -> Result<usize, String> {
+    if let Ok(count) = count_text.parse::<usize>() {
+        return Ok(count);
+    }
+
+    let variable_value = scope.lookup(count_text).or_else(|| config.user_variables.get(count_text).cloned());
+    match variable_value {
+        Some(value) => match value.trim().parse::<usize>() {
+            Ok(count) => Ok(count),
+            Err(_) => Err(format!("REPEAT_X_TIMES count variable {:} has a value of {:} which is not a number. ", count_text, value))
+        },
+        None => Err(format!("REPEAT_X_TIMES count {{{:}}} is not a number or the name of a variable. ", count_text))
+    }
+}
+
+fn loop_iteration(token: &Token, scope: &TemplateScope) -> Result<LoopIteration, TokenError> {
+    match scope.current_iteration() {
+        Some(iteration) => Ok(iteration),
+        None => Err(TokenError::new(&format!("{:} can only be used inside of REPEAT_X_TIMES, FOR_EACH_FILE_IN_DIR or FOR_EACH_ITEM. ", token.id)))
+    }
 }
 
-fn replace_harvest_variables(
-    line: &str, 
-    file_context: &FileContext, 
-    parent_file_context: &FileContext, 
-    harvest_location: &Option<String>, 
-    config: &Config) -> String {
-   
+fn define_template_variable(token: &Token, scope: &mut TemplateScope) -> Result<String, TokenError> {
 
-    // @todo: This function can go away now that the refactor is almost done 
-    // @Hack: This shouldnt be getting all this stuff passed down. This will all hopefully go away with the file_context stuff being worked on. 
-    let line_with_evaluated_variables = replace_sub_symbols(
-        &line.to_string(), 
-        &file_context,
-        Some(parent_file_context), 
-        harvest_location, config
-    );
+    let variables = token.variables.clone().unwrap();
+    if variables.len() != 2 {
+        return Err(TokenError::new("DEFINE_TEMPLATE_VAR needs a name and a value. ie: []DEFINE_TEMPLATE_VAR{NAME|||value}[]"));
+    }
+
+    let name = variables[0].rebuild_string().trim().to_string();
+    if name.is_empty() {
+        return Err(TokenError::with_argument("DEFINE_TEMPLATE_VAR was given an empty variable name. ", 0));
+    }
 
-    String::from(line_with_evaluated_variables)
+    scope.define(&name, &variables[1].rebuild_string());
+
+    // The definition itself doesn't place anything in the file.
+    Ok(String::new())
+}
+
+fn template_variable(name: &str, scope: &TemplateScope) -> Result<String, TokenError> {
+    let name = name.trim();
+
+    info!("Looking for template variable: {:}", name);
+
+    match scope.lookup(name) {
+        Some(value) => Ok(value),
+        None => Err(TokenError::with_argument(&format!("No template variable with the name of {:} has been defined at this point in the template. ", name), 0)
+                    .with_suggestion(did_you_mean(name, &scope.names())))
+    }
 }
 
-fn file_name_as_type_with_args(file_name: &str, token: &Token) -> Option<String> {
+fn file_name_as_type_with_args(file_name: &str, token: &Token) -> Result<String, TokenError> {
     if !token.has_variables() {
-        error!("- INTERNAL - Got an empty list of variables for file_name_as_type_with_args");
-        None
+        Err(TokenError::new("- INTERNAL - Got an empty list of variables for file_name_as_type_with_args"))
     } else {
-        // @todo: Make this use a default formatting from config file so that the user can define this. 
-        match format_append_and_remove_but_ensure_formatted_to_type(
-                file_name, 
-                &token.variables.clone().unwrap()[0].variable_list, 
-                "pascal".to_string()) {
-            Some(value) => Some(value),
-            None => Some(file_name.to_string())
-        }
+        // @todo: Make this use a default formatting from config file so that the user can define this.
+        format_append_and_remove_but_ensure_formatted_to_type(
+                file_name,
+                &token.variables.clone().unwrap()[0].variable_list,
+                "pascal".to_string())
+            .map_err(format_error)
     }
 }
 
-fn file_name_without_extension_with_args(file_name: &str, token: &Token) -> Option<String> {
+fn file_name_without_extension_with_args(file_name: &str, token: &Token) -> Result<String, TokenError> {
     if !token.has_variables() {
-        error!("- INTERNAL - Got an empty list of variables for file_name_without_extension_with_args");
-        None
+        Err(TokenError::new("- INTERNAL - Got an empty list of variables for file_name_without_extension_with_args"))
     } else {
-        match format_append_and_remove(file_name, &token.variables.clone().unwrap()[0].variable_list) {
-            Some(value) => Some(value),
-            None => Some(file_name.to_string())
-        }
+        format_append_and_remove(file_name, &token.variables.clone().unwrap()[0].variable_list)
+            .map_err(format_error)
     }
 }
 
-fn file_name_with_args(file_name: &str, token: &Token, extension: &str) -> Option<String> {
+fn file_name_with_args(file_name: &str, token: &Token, extension: &str) -> Result<String, TokenError> {
 
     if !token.has_variables() {
-        // @todo: Format this to default typing as defined in config file. 
-        Some(file_name.to_string())
+        // @todo: Format this to default typing as defined in config file.
+        Ok(file_name.to_string())
     } else {
         match format_append_and_remove(file_name, &token.variables.clone().unwrap()[0].variable_list) {
-            Some(value) => Some(value + "." + extension),
-            None => Some(file_name.to_string() + "." + extension)
+            Ok(value) => Ok(value + "." + extension),
+            Err(error) => Err(format_error(error))
         }
     }
 }
 
-fn user_variable(variable: &str, user_variable_map: &HashMap<String, String>) -> Option<String> {
+fn user_variable(variable: &str, user_variable_map: &HashMap<String, String>) -> Result<String, TokenError> {
 
     info!("Looking for user variable: {:}", variable);
 
@@ -264,103 +924,254 @@ fn user_variable(variable: &str, user_variable_map: &HashMap<String, String>) ->
 
         info!("Found variable {:} to have a value of {:}", variable, variable_value);
 
-        return Some(variable_value);
-    } 
+        return Ok(variable_value);
+    }
+
+    let variable_names: Vec<&String> = user_variable_map.keys().collect();
+    Err(TokenError::with_argument(&format!("No user variable with the name of {:} exists in configuration file. ", variable), 0)
+        .with_suggestion(did_you_mean(variable, &variable_names)))
+}
+
+// []ENV{name|||default|||required}[], both the default and the required flag can be left off.
+fn environment_variable(token: &Token) -> Result<String, TokenError> {
+    let name = token.get_variable_as_string(0).trim().to_string();
+    if name.is_empty() {
+        return Err(TokenError::with_argument("ENV needs the name of an environment variable. ie: []ENV{CI_PROJECT|||unknown}[]", 0));
+    }
+
+    let default_value = optional_variable(token, 1).filter(|default_value| !default_value.is_empty());
 
-    warn!("No user variable with the name of {:} exists in configuration file. ", variable);
+    let is_required = match optional_variable(token, 2).as_deref().map(str::trim) {
+        None | Some("") => false,
+        Some("required") => true,
+        Some(flag) => return Err(TokenError::with_argument(&format!("{:} is not a flag ENV knows about, the only flag is required. ", flag), 2)
+                        .with_suggestion(did_you_mean(flag, &["required"])))
+    };
 
-    None
+    info!("Looking for environment variable: {:}", name);
+
+    match std::env::var(&name) {
+        Ok(value) => Ok(value),
+        Err(_) if is_required => Err(TokenError::with_argument(&format!("Required environment variable {:} is not set. ", name), 0)),
+        Err(_) => match default_value {
+            Some(default_value) => Ok(default_value),
+            None => {
+                warn!("Environment variable {:} is not set and has no default, leaving it blank. ", name);
+                Ok(String::new())
+            }
+        }
+    }
 }
 
-fn import_file(token: &Token) -> Option<String> {
+// []PROMPT{question|||default|||choice, choice}[], the default and choices can be left off.
+fn prompt(token: &Token, state: &mut EvaluationState) -> Result<String, TokenError> {
+    let question = token.get_variable_as_string(0).trim().to_string();
+    if question.is_empty() {
+        return Err(TokenError::with_argument("PROMPT needs a question to ask. ie: []PROMPT{Base class|||QObject}[]", 0));
+    }
 
-    use std::fs::read_to_string;
+    let default_answer = optional_variable(token, 1).map(|default_answer| default_answer.trim().to_string()).filter(|default_answer| !default_answer.is_empty());
+    let choices = match optional_variable(token, 2) {
+        Some(_) => token.get_variable_at(2),
+        None => Vec::new()
+    };
 
-    if !token.has_variables() {
-        error!("Import token found but no template name was found. ");
+    let answer = match &mut state.prompter {
+        Some(prompter) => prompter.ask(&question, default_answer.as_deref(), &choices),
+        None => Prompter::non_interactive(HashMap::new()).ask(&question, default_answer.as_deref(), &choices)
+    };
+
+    answer.map_err(|message| TokenError::with_argument(&message, 0))
+}
+
+// IMPORT looks for files in the templates directory.
+fn template_directory_path(file_name: &str) -> String {
+    get_template_directory().unwrap() + PLATFORM_SEPARATOR_SLASH + file_name.trim()
+}
+
+// RELATIVE_IMPORT looks for files next to the file that is importing them, which could itself be an import.
+fn relative_path(file_name: &str, state: &EvaluationState) -> String {
+    use std::path::Path;
+
+    let importing_directory = state.current_path()
+        .and_then(|path| Path::new(path).parent())
+        .filter(|directory| !directory.as_os_str().is_empty());
+
+    match importing_directory {
+        Some(directory) => directory.join(file_name.trim()).to_string_lossy().to_string(),
+        None => file_name.trim().to_string()
     }
+}
+
+// Imported files are evaluated as templates for the same file as the one importing them.
+fn import_file(
+    import_path: &str,
+    file_context: &FileContext,
+    parent_file_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> Result<String, TokenError> {
 
-    let variable = token.get_variable_as_string(0);
+    use std::fs::read_to_string;
 
-    let import_path = get_template_directory().unwrap() + PLATFORM_SEPARATOR_SLASH + &variable; 
     info!("Attempting to import file: {:}", import_path);
 
+    state.can_include(import_path).map_err(|message| TokenError::with_argument(&message, 0))?;
+
+    match read_to_string(import_path) {
+        Ok(file_contents) => Ok(replace_sub_symbols(import_path, &file_contents, file_context, parent_file_context, harvest_location, config, state)),
+        Err(e) => Err(TokenError::with_argument(&format!("Failed to load file {:} for import. Make sure that the file exists and that the path is correct. {:}", import_path, e), 0))
+    }
+}
+
+fn call_partial(
+    token: &Token,
+    file_context: &FileContext,
+    parent_file_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> Result<String, TokenError> {
 
-    let file_contents = read_to_string(import_path);
-    if file_contents.is_err() {
-        error!("Failed to load file for import. Make sure that the file exists and that the path is correct. ");
-        return None;
+    let partial_name = token.get_variable_as_string(0).trim().to_string();
+    if partial_name.is_empty() {
+        return Err(TokenError::with_argument("CALL needs the name of a partial. ie: []CALL{getter|||name=count}[]", 0));
     }
 
-    return Some(file_contents.unwrap());
+    let arguments = partial_arguments(token)?;
+    let partial = load_partial(&partial_name)?;
+
+    evaluate_partial(&partial, &arguments, file_context, parent_file_context, harvest_location, config, state)
 }
 
-fn create_banner(
-    banner_symbol: &str, 
-    banner_text: &str, 
-    file_context: &FileContext, 
-    harvest_location: &Option<String>, 
-    config: &Config) -> Option<String> {
+// Everything after the partial's name is an argument written as name=value.
+fn partial_arguments(token: &Token) -> Result<Vec<(String, String)>, TokenError> {
+    let mut arguments: Vec<(String, String)> = Vec::new();
 
-    let message: String = replace_sub_symbols(&banner_text.to_string(), file_context, None, harvest_location, config);
+    for (index, variable) in token.variables.clone().unwrap().iter().enumerate().skip(1) {
+        let argument = variable.rebuild_string();
+        match argument.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => arguments.push((name.trim().to_string(), value.trim().to_string())),
+            _ => return Err(TokenError::with_argument(&format!("Arguments to a partial are written as name=value, got {:}. ", argument.trim()), index))
+        }
+    }
+
+    Ok(arguments)
+}
+
+struct Partial {
+    path: String,
+    text: String
+}
+
+// Partials live in the partials directory inside of the templates directory, ie: templates/partials/getter.partial
+fn load_partial(partial_name: &str) -> Result<Partial, TokenError> {
+
+    use std::fs::read_to_string;
+
+    let partial_directory = get_template_directory().unwrap() + PLATFORM_SEPARATOR_SLASH + PARTIAL_DIRECTORY;
+    let partial_path = partial_directory.clone() + PLATFORM_SEPARATOR_SLASH + partial_name + ".partial";
+    info!("Attempting to load partial: {:}", partial_path);
+
+    match read_to_string(&partial_path) {
+        Ok(text) => {
+            // Partials are placed where they are called, the line ending at the end of the file would leave a blank line behind.
+            let text = text.strip_suffix('\n').map(|text| text.strip_suffix('\r').unwrap_or(text)).unwrap_or(&text).to_string();
+            Ok(Partial { path: partial_path, text })
+        },
+        Err(e) => {
+            let known_partials: Vec<String> = harvest_files_from_dir(&Some(partial_directory), &vec!["partial".to_string()])
+                .iter()
+                .filter_map(|file| file.file_name.clone())
+                .collect();
+
+            Err(TokenError::with_argument(&format!("Failed to load partial {:}. Make sure that the file exists. {:}", partial_path, e), 0)
+                .with_suggestion(did_you_mean(partial_name, &known_partials)))
+        }
+    }
+}
+
+// The arguments become template variables that only exist while the partial is being evaluated.
+fn evaluate_partial(
+    partial: &Partial,
+    arguments: &[(String, String)],
+    file_context: &FileContext,
+    parent_file_context: Option<&FileContext>,
+    harvest_location: &Option<String>,
+    config: &Config,
+    state: &mut EvaluationState) -> Result<String, TokenError> {
+
+    state.can_include(&partial.path).map_err(|message| TokenError::with_argument(&message, 0))?;
+
+    state.scope.push_frame();
+    for (name, value) in arguments {
+        state.scope.define(name, value);
+    }
+
+    let replacement_value = replace_sub_symbols(&partial.path, &partial.text, file_context, parent_file_context, harvest_location, config, state);
+    state.scope.pop_frame();
+
+    Ok(replacement_value)
+}
+
+fn create_banner(banner_symbol: &str, message: &str) -> String {
 
     info!("Creating banner with symbol {{{:}}}, and message {{{:}}}", banner_symbol, message);
 
-    // +4 to add an extra symbol and space to the message. 
+    // +4 to add an extra symbol and space to the message.
     let mut banner: String = String::new();
     for _ in 0..message.len()+4 {
         banner += banner_symbol;
     }
     banner += PLATFORM_LINE_ENDING;
 
-    banner = banner + banner_symbol + " " + &message + " " + banner_symbol + PLATFORM_LINE_ENDING;
+    banner = banner + banner_symbol + " " + message + " " + banner_symbol + PLATFORM_LINE_ENDING;
 
     for _ in 0..message.len()+4 {
         banner += banner_symbol;
     }
     banner += PLATFORM_LINE_ENDING;
 
-    Some(banner)
+    banner
 }
 
-fn find_partner_file(file_context: &FileContext, partner_file_map: &HashMap<String, String>) -> Option<String> {
-
-    if file_context.extension == "".to_string() {
-        error!("Cannot match partner file to file without extension. PARTNER_FILE token means nothing in this context. ");
-        return None;
-    }
+fn find_partner_file(file_context: &FileContext, partner_file_map: &HashMap<String, String>) -> Result<String, TokenError> {
 
-    let mut partner_file_extension: Option<String> = None;
-    if partner_file_map.contains_key(&file_context.extension) {
-        partner_file_extension = Some(partner_file_map[&file_context.extension].clone())
+    if file_context.extension.is_empty() {
+        return Err(TokenError::new("Cannot match partner file to file without extension. PARTNER_FILE token means nothing in this context. "));
     }
 
-    if partner_file_extension.is_some() {
-        info!("Matched file extension {:} to {:?} for PARTNER_FILE", &file_context.extension, partner_file_extension);
-        Some(file_context.name.to_string() + "." + &partner_file_extension.unwrap())
-    } else {
-        warn!("No partner file defined in configuration for file {:}", file_context.extension);
-        None
+    match partner_file_map.get(&file_context.extension) {
+        Some(partner_file_extension) => {
+            info!("Matched file extension {:} to {:?} for PARTNER_FILE", &file_context.extension, partner_file_extension);
+            Ok(file_context.name.to_string() + "." + partner_file_extension)
+        },
+        None => Err(TokenError::new(&format!("No partner file defined in configuration for file extension {:}", file_context.extension)))
     }
 }
 
-fn this_files_name(token: &Token, file_context: &FileContext, parent_context: Option<&FileContext>) -> Option<String> {
+fn this_files_name(token: &Token, file_context: &FileContext, parent_context: Option<&FileContext>) -> Result<String, TokenError> {
 
-    // If we have no parent context, this defaults to the file context we do have. 
+    // If we have no parent context, this defaults to the file context we do have.
     let context = match parent_context {
-        Some(c) => c, 
+        Some(c) => c,
         None => file_context
     };
 
     file_name_with_args(&context.name, token, &context.extension)
 }
 
+// Evaluates text as if it were a whole template, tests don't have a template file to work with.
+#[cfg(test)]
+fn evaluate_test_template(text: &str, file_context: &FileContext, harvest_location: &Option<String>, config: &Config) -> String {
+    replace_sub_symbols("test_template", text, file_context, None, harvest_location, config, &mut EvaluationState::new())
+}
+
 #[test]
 fn file_name_as_type_with_subtraction() {
     let expected_string = "Builder".to_string();
     let test_string = "BuilderManager";
 
-    assert_eq!(Some(expected_string), 
+    assert_eq!(Ok(expected_string), 
         file_name_as_type_with_args(test_string, 
         &Token {
             id: "FILE_NAME_AS_TYPE".to_string(),
@@ -376,7 +1187,7 @@ fn file_name_as_type_with_illegal_subtraction_returns_original_string() {
     let test_string = "Builder";
 
 
-    assert_eq!(Some(expected_string), 
+    assert_eq!(Ok(expected_string), 
     file_name_as_type_with_args(test_string, 
     &Token {
         id: "FILE_NAME_AS_TYPE".to_string(),
@@ -393,7 +1204,7 @@ fn file_name_as_type_with_subtraction_and_case_change() {
     let test_string = "BuilderManager";
 
 
-    assert_eq!(Some(expected_string), 
+    assert_eq!(Ok(expected_string), 
         file_name_as_type_with_args(test_string, 
         &Token {
             id: "FILE_NAME_AS_TYPE".to_string(),
@@ -413,7 +1224,7 @@ fn file_name_as_type_with_subtraction_and_addition() {
     let test_string = "BuilderManager";
 
 
-    assert_eq!(Some(expected_string), 
+    assert_eq!(Ok(expected_string), 
         file_name_as_type_with_args(test_string, 
         &Token {
             id: "FILE_NAME_AS_TYPE".to_string(),
@@ -433,7 +1244,7 @@ fn file_name_as_type_with_subtraction_and_addition_and_case_change() {
     let test_string = "BuilderManager";
 
 
-    assert_eq!(Some(expected_string), 
+    assert_eq!(Ok(expected_string), 
         file_name_as_type_with_args(test_string, 
         &Token {
             id: "FILE_NAME_AS_TYPE".to_string(),
@@ -454,7 +1265,7 @@ fn file_name_with_subtraction_and_addition_and_case_change() {
     let test_string = "BuilderManager";
 
     assert_eq!(
-        Some(expected_string), 
+        Ok(expected_string), 
         file_name_with_args(test_string, 
         &Token {
             id: "FILE_NAME_AS_TYPE".to_string(),
@@ -468,3 +1279,370 @@ fn file_name_with_subtraction_and_addition_and_case_change() {
         }, "h"));
 }
 
+
+
+#[test]
+fn if_token_picks_branch_on_platform() {
+    let mut file_context = FileContext::blank();
+    file_context.enumerations.set("platform", "windows");
+
+    let test_string = "[]IF{PLATFORM == windows|||#include <windows.h>|||#include <unistd.h>}[]";
+    assert_eq!("#include <windows.h>", evaluate_test_template(test_string, &file_context, &None, &Config::default()));
+
+    file_context.enumerations.set("platform", "linux");
+    assert_eq!("#include <unistd.h>", evaluate_test_template(test_string, &file_context, &None, &Config::default()));
+}
+
+#[test]
+fn enumeration_values_are_read_from_their_table() {
+    let mut config = Config::default();
+    config.platform_list = serde_json::from_str(r#"[ { "name": "windows", "export_macro": "__declspec(dllexport)", "separator": "\\" }, "linux" ]"#).unwrap();
+
+    let mut file_context = FileContext::blank();
+    file_context.enumerations.set("platform", "windows");
+
+    let test_string = "[]PLATFORM_VALUE{export_macro}[] []PLATFORM_VALUE{separator}[] []IF{PLATFORM_VALUE{export_macro}|||exported|||hidden}[]";
+    assert_eq!("__declspec(dllexport) \\ exported", evaluate_test_template(test_string, &file_context, &None, &config));
+
+    file_context.enumerations.set("platform", "linux");
+    let mut state = EvaluationState::new();
+    assert_eq!("ERR", replace_sub_symbols("template.h", "[]PLATFORM_VALUE{export_macro}[]", &file_context, None, &None, &config, &mut state));
+    assert_eq!("hidden", evaluate_test_template("[]IF{PLATFORM_VALUE{export_macro}|||exported|||hidden}[]", &file_context, &None, &config));
+
+    file_context.enumerations.set("platform", "windows");
+    let mut state = EvaluationState::new();
+    replace_sub_symbols("template.h", "[]PLATFORM_VALUE{export_marco}[]", &file_context, None, &None, &config, &mut state);
+    assert_eq!(Some("export_macro".to_string()), state.diagnostics[0].suggestion);
+
+    assert_eq!("[]LANGUAGE_VALUE{display_name}[]", evaluate_test_template("[]LANGUAGE_VALUE{display_name}[]", &file_context, &None, &config));
+}
+
+#[test]
+fn if_token_nests_and_checks_user_variables() {
+    let mut file_context = FileContext::blank();
+    file_context.enumerations.set("language", "fr");
+
+    let test_string = "[]IF{USER_VAR{LOOPBACK_ADDR}|||[]IF{LANGUAGE == en|||hello|||bonjour}[]|||nothing}[]";
+    assert_eq!("bonjour", evaluate_test_template(test_string, &file_context, &None, &Config::default()));
+
+    let test_string = "[]IF{USER_VAR{NOT_A_VARIABLE}|||set}[]";
+    assert_eq!("", evaluate_test_template(test_string, &file_context, &None, &Config::default()));
+}
+
+
+#[test]
+fn template_variable_is_defined_from_other_tokens() {
+    let mut file_context = FileContext::blank();
+    file_context.name = "model".to_string();
+
+    let test_string = "[]DEFINE_TEMPLATE_VAR{BUILDER|||[]FILE_NAME_AS_TYPE{+Builder}[]}[]class []TEMPLATE_VAR{BUILDER}[] : []TEMPLATE_VAR{ BUILDER }[]Base";
+    assert_eq!("class ModelBuilder : ModelBuilderBase", evaluate_test_template(test_string, &file_context, &None, &Config::default()));
+}
+
+#[test]
+fn undefined_template_variable_is_an_error() {
+    let test_string = "[]TEMPLATE_VAR{NOT_DEFINED}[]";
+    assert_eq!("ERR", evaluate_test_template(test_string, &FileContext::blank(), &None, &Config::default()));
+}
+
+
+#[test]
+fn repeat_x_times_repeats_with_index_and_number() {
+    let test_string = "[]REPEAT_X_TIMES{3|||REG_[]INDEX[] = []NUMBER[],}[]";
+    assert_eq!("REG_0 = 1,REG_1 = 2,REG_2 = 3,", evaluate_test_template(test_string, &FileContext::blank(), &None, &Config::default()));
+}
+
+#[test]
+fn repeat_x_times_count_from_variable_and_nested_loops() {
+    let mut config = Config::default();
+    config.user_variables.insert("REGISTER_COUNT".to_string(), "2".to_string());
+
+    let test_string = "[]REPEAT_X_TIMES{REGISTER_COUNT|||[]REPEAT_X_TIMES{[]USER_VAR{REGISTER_COUNT}[]|||[]INDEX[]}[]-[]INDEX[] }[]";
+    assert_eq!("01-0 01-1 ", evaluate_test_template(test_string, &FileContext::blank(), &None, &config));
+}
+
+#[test]
+fn repeat_x_times_count_is_capped() {
+    let test_string = "[]REPEAT_X_TIMES{1000000000|||line}[]";
+
+    let mut state = EvaluationState::new();
+    let result = replace_sub_symbols("template.h", test_string, &FileContext::blank(), None, &None, &Config::default(), &mut state);
+
+    assert_eq!("ERR", result);
+    assert_eq!(1, state.diagnostics.len());
+}
+
+
+#[test]
+fn if_token_checks_first_and_last_iteration() {
+    let test_string = "[]REPEAT_X_TIMES{3|||[]IF{IS_FIRST|||(}[][]INDEX[][]IF{IS_LAST|||)|||, }[]}[]";
+    assert_eq!("(0, 1, 2)", evaluate_test_template(test_string, &FileContext::blank(), &None, &Config::default()));
+
+    let test_string = "[]IF{IS_FIRST|||outside of a loop}[]";
+    assert_eq!("ERR", evaluate_test_template(test_string, &FileContext::blank(), &None, &Config::default()));
+}
+
+#[test]
+fn for_each_file_in_dir_separates_files_and_numbers_them() {
+    let harvest_dir = std::env::temp_dir().join(format!("tt_for_each_separator_test_{:}", std::process::id()));
+    std::fs::create_dir_all(&harvest_dir).unwrap();
+    std::fs::write(harvest_dir.join("a.h"), "").unwrap();
+    std::fs::write(harvest_dir.join("b.h"), "").unwrap();
+    std::fs::write(harvest_dir.join("c.cpp"), "").unwrap();
+    let harvest_location = Some(harvest_dir.to_str().unwrap().to_string());
+
+    let test_string = "[]FOR_EACH_FILE_IN_DIR{h|||[]NUMBER[]:[]FILE_NAME[]|||, }[]";
+    let result = evaluate_test_template(test_string, &FileContext::blank(), &harvest_location, &Config::default());
+
+    let mut items: Vec<&str> = result.split(", ").collect();
+    items.sort();
+    assert!(items == vec!["1:a.h", "2:b.h"] || items == vec!["1:b.h", "2:a.h"], "Unexpected output {:}", result);
+
+    let test_string = "[]EACH_FILE_IN_DIR{cpp, h||| \\}[]";
+    let result = evaluate_test_template(test_string, &FileContext::blank(), &harvest_location, &Config::default());
+    assert_eq!(2, result.matches(" \\").count());
+
+    std::fs::remove_dir_all(&harvest_dir).unwrap();
+}
+
+
+#[test]
+fn substituted_values_are_not_evaluated_again() {
+    let mut config = Config::default();
+    config.user_variables.insert("LOOKS_LIKE_A_TOKEN".to_string(), "[]USER_VAR{LOOKS_LIKE_A_TOKEN}[]".to_string());
+
+    let test_string = "[]USER_VAR{LOOKS_LIKE_A_TOKEN}[] []PLATFORM[]";
+    assert_eq!("[]USER_VAR{LOOKS_LIKE_A_TOKEN}[] []PLATFORM[]", evaluate_test_template(test_string, &FileContext::blank(), &None, &config));
+}
+
+#[test]
+fn substituted_values_do_not_change_parameters_or_conditions() {
+    let mut config = Config::default();
+    config.user_variables.insert("SUFFIX".to_string(), "Item,+Extra".to_string());
+    config.user_variables.insert("KEY".to_string(), "A == B".to_string());
+    config.user_variables.insert("A == B".to_string(), "set".to_string());
+    config.user_variables.insert("TARGETS".to_string(), "linux,windows".to_string());
+
+    let mut file = FileContext::blank();
+    file.name = "button".to_string();
+    file.extension = "h".to_string();
+
+    assert_eq!("ButtonItem,+Extra", evaluate_test_template("[]FILE_NAME_AS_TYPE{+[]USER_VAR{SUFFIX}[]}[]", &file, &None, &config));
+    assert_eq!("ButtonItemExtra", evaluate_test_template("[]FILE_NAME_AS_TYPE{+Item,+Extra}[]", &file, &None, &config));
+
+    assert_eq!("yes", evaluate_test_template("[]IF{USER_VAR{[]USER_VAR{KEY}[]}|||yes|||no}[]", &file, &None, &config));
+    assert_eq!("yes", evaluate_test_template("[]IF{USER_VAR{TARGETS} == []USER_VAR{TARGETS}[]|||yes|||no}[]", &file, &None, &config));
+    assert_eq!("no", evaluate_test_template("[]IF{USER_VAR{TARGETS} == linux, windows|||yes|||no}[]", &file, &None, &config));
+}
+
+#[test]
+fn unknown_token_is_reported_with_its_position() {
+    let test_string = "#pragma once\n\tclass []NOT_A_TOKEN[] {};";
+
+    let mut state = EvaluationState::new();
+    let result = replace_sub_symbols("template.h", test_string, &FileContext::blank(), None, &None, &Config::default(), &mut state);
+
+    assert_eq!("#pragma once\n\tclass ERR {};", result);
+    assert_eq!(1, state.diagnostics.len());
+    assert_eq!("template.h", state.diagnostics[0].template_path);
+    assert_eq!(Position { line: 2, column: 8 }, state.diagnostics[0].position);
+    assert_eq!(15, state.diagnostics[0].highlight_length);
+}
+
+#[test]
+fn bad_argument_is_pointed_at() {
+    let test_string = "[]BANNER{*|||hi}[] []USER_VAR{TEAM}[]";
+
+    let mut state = EvaluationState::new();
+    replace_sub_symbols("template.h", test_string, &FileContext::blank(), None, &None, &Config::default(), &mut state);
+
+    assert_eq!(1, state.diagnostics.len());
+    assert_eq!(Some("argument 1 {TEAM}".to_string()), state.diagnostics[0].argument);
+    assert_eq!(Position { line: 1, column: 31 }, state.diagnostics[0].position);
+    assert_eq!(4, state.diagnostics[0].highlight_length);
+}
+
+#[test]
+fn invalid_token_is_reported() {
+    let mut state = EvaluationState::new();
+    let result = replace_sub_symbols("template.h", "a []file_name[] b", &FileContext::blank(), None, &None, &Config::default(), &mut state);
+
+    assert_eq!("a ERR b", result);
+    assert_eq!(1, state.diagnostics.len());
+    assert_eq!(Position { line: 1, column: 3 }, state.diagnostics[0].position);
+}
+
+#[test]
+fn misspelled_names_get_suggestions() {
+    let test_string = "[]FILE_NAM[] []FILE_NAME{kebab}[] []USER_VAR{LOOPBACK_ADR}[]";
+
+    let mut config = Config::default();
+    config.user_variables.insert("LOOPBACK_ADDR".to_string(), "127.0.0.1".to_string());
+
+    let mut state = EvaluationState::new();
+    replace_sub_symbols("template.h", test_string, &FileContext::blank(), None, &None, &config, &mut state);
+
+    let suggestions: Vec<Option<String>> = state.diagnostics.iter().map(|diagnostic| diagnostic.suggestion.clone()).collect();
+    assert_eq!(vec![
+        Some("FILE_NAME".to_string()),
+        Some("kabob".to_string()),
+        Some("LOOPBACK_ADDR".to_string())
+    ], suggestions);
+}
+
+#[test]
+fn filters_format_any_token() {
+    let mut config = Config::default();
+    config.user_variables.insert("TEAM".to_string(), "core_tools".to_string());
+
+    let mut file_context = FileContext::blank();
+    file_context.enumerations.set("platform", "linux");
+
+    let test_string = "[]USER_VAR{TEAM}|kabob[] []PLATFORM|upper|+_H[] []REPEAT_X_TIMES{2|||a_}|pascal[]";
+    assert_eq!("core-tools LINUX_H AA", evaluate_test_template(test_string, &file_context, &None, &config));
+
+    let mut state = EvaluationState::new();
+    let result = replace_sub_symbols("template.h", "[]PLATFORM|kebab[]", &file_context, None, &None, &config, &mut state);
+    assert_eq!("ERR", result);
+    assert_eq!(Some("kabob".to_string()), state.diagnostics[0].suggestion);
+}
+
+#[test]
+fn partial_arguments_are_named() {
+    let token = Token::from_string("[]CALL{getter|||name=count||| type = int }[]").unwrap();
+
+    assert_eq!(Ok(vec![
+        ("name".to_string(), "count".to_string()),
+        ("type".to_string(), "int".to_string())
+    ]), partial_arguments(&token));
+
+    let token = Token::from_string("[]CALL{getter|||name=count|||int}[]").unwrap();
+    assert_eq!(Some(2), partial_arguments(&token).unwrap_err().argument);
+}
+
+#[test]
+fn partial_is_evaluated_with_arguments_and_caller_context() {
+    let partial = Partial {
+        path: "getter.partial".to_string(),
+        text: "[]TEMPLATE_VAR{type}[] []FILE_NAME_AS_TYPE[]::get_[]TEMPLATE_VAR{name}[]() const;".to_string()
+    };
+    let arguments = vec![
+        ("name".to_string(), "count".to_string()),
+        ("type".to_string(), "int".to_string())
+    ];
+
+    let mut file_context = FileContext::blank();
+    file_context.name = "model_builder".to_string();
+
+    let mut state = EvaluationState::new();
+    let result = evaluate_partial(&partial, &arguments, &file_context, None, &None, &Config::default(), &mut state);
+
+    assert_eq!(Ok("int ModelBuilder::get_count() const;".to_string()), result);
+
+    // Arguments only exist inside of the partial.
+    assert_eq!(None, state.scope.lookup("name"));
+}
+
+#[test]
+fn partial_calling_itself_is_an_error() {
+    let mut state = EvaluationState::new();
+    state.push_source("getter.partial", "[]CALL{getter}[]");
+
+    let partial = Partial {
+        path: "getter.partial".to_string(),
+        text: "[]CALL{getter}[]".to_string()
+    };
+    let result = evaluate_partial(&partial, &Vec::new(), &FileContext::blank(), None, &None, &Config::default(), &mut state);
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn relative_imports_are_nested_and_evaluated() {
+    let import_dir = std::env::temp_dir().join(format!("tt_relative_import_test_{:}", std::process::id()));
+    std::fs::create_dir_all(import_dir.join("parts")).unwrap();
+    std::fs::write(import_dir.join("parts").join("outer.txt"), "outer []FILE_NAME_AS_TYPE[] []RELATIVE_IMPORT{inner.txt}[]").unwrap();
+    std::fs::write(import_dir.join("parts").join("inner.txt"), "inner []PLATFORM[]").unwrap();
+    std::fs::write(import_dir.join("loop_a.txt"), "a []RELATIVE_IMPORT{loop_b.txt}[]").unwrap();
+    std::fs::write(import_dir.join("loop_b.txt"), "b []RELATIVE_IMPORT{./loop_a.txt}[]").unwrap();
+    for index in 0..40 {
+        std::fs::write(import_dir.join(format!("deep_{:}.txt", index)), format!("[]RELATIVE_IMPORT{{deep_{:}.txt}}[]", index + 1)).unwrap();
+    }
+    let template_path = import_dir.join("template.h").to_str().unwrap().to_string();
+
+    let mut file_context = FileContext::blank();
+    file_context.name = "model".to_string();
+    file_context.enumerations.set("platform", "linux");
+
+    let mut state = EvaluationState::new();
+    let result = replace_sub_symbols(&template_path, "[]RELATIVE_IMPORT{parts/outer.txt}[]", &file_context, None, &None, &Config::default(), &mut state);
+    assert_eq!("outer ModelLinux inner linux", result);
+    assert!(state.diagnostics.is_empty());
+
+    let mut state = EvaluationState::new();
+    let result = replace_sub_symbols(&template_path, "[]RELATIVE_IMPORT{loop_a.txt}[]", &file_context, None, &None, &Config::default(), &mut state);
+    assert_eq!("a b ERR", result);
+    assert!(state.diagnostics[0].message.starts_with("Import cycle found"));
+
+    let mut state = EvaluationState::new();
+    replace_sub_symbols(&template_path, "[]RELATIVE_IMPORT{deep_0.txt}[]", &file_context, None, &None, &Config::default(), &mut state);
+    assert!(state.diagnostics[0].message.starts_with("Imports are nested more than"));
+
+    let _ = std::fs::remove_dir_all(&import_dir);
+}
+
+#[test]
+fn env_token_uses_defaults_and_required_flag() {
+    std::env::set_var("TT_ENV_TOKEN_TEST", "tt");
+    std::env::remove_var("TT_ENV_TOKEN_TEST_MISSING");
+
+    let test_string = "[]ENV{TT_ENV_TOKEN_TEST}[] []ENV{TT_ENV_TOKEN_TEST_MISSING|||unknown}[] []ENV{TT_ENV_TOKEN_TEST_MISSING}[]|[]IF{ENV{TT_ENV_TOKEN_TEST}|||set}[]";
+    assert_eq!("tt unknown |set", evaluate_test_template(test_string, &FileContext::blank(), &None, &Config::default()));
+
+    let mut state = EvaluationState::new();
+    let result = replace_sub_symbols("template.h", "[]ENV{TT_ENV_TOKEN_TEST_MISSING||||||required}[] []ENV{TT_ENV_TOKEN_TEST|||||| requierd}[]", &FileContext::blank(), None, &None, &Config::default(), &mut state);
+    assert_eq!("ERR ERR", result);
+    assert_eq!(Some("argument 1 {TT_ENV_TOKEN_TEST_MISSING}".to_string()), state.diagnostics[0].argument);
+    assert_eq!(Some("required".to_string()), state.diagnostics[1].suggestion);
+}
+
+#[test]
+fn prompts_are_answered_once_for_every_file() {
+    let mut preset_answers = HashMap::new();
+    preset_answers.insert("License".to_string(), "GPL".to_string());
+    let mut prompter = Prompter::non_interactive(preset_answers);
+
+    let test_string = "[]PROMPT{Base class|||QObject}[] []PROMPT{ License |||MIT|||MIT, GPL}[] []PROMPT{Base class|||QWidget}[]";
+    let result = replace_sub_symbols("template.h", test_string, &FileContext::blank(), None, &None, &Config::default(), &mut EvaluationState::with_prompter(&mut prompter));
+    assert_eq!("QObject GPL QObject", result);
+
+    let mut state = EvaluationState::with_prompter(&mut prompter);
+    let result = replace_sub_symbols("template.h", "[]PROMPT{Description}[]", &FileContext::blank(), None, &None, &Config::default(), &mut state);
+    assert_eq!("ERR", result);
+    assert_eq!(1, state.diagnostics.len());
+}
+
+#[test]
+fn data_is_read_by_path_and_looped_over() {
+    let data: Value = serde_json::from_str(r#"{
+        "name": "Telemetry",
+        "fields": [ { "name": "speed", "type": "float", "optional": true }, { "name": "heading", "type": "int" } ],
+        "tags": [ "a", "b" ]
+    }"#).unwrap();
+
+    let test_string = "class []DATA{name}[] {[]FOR_EACH_ITEM{fields|||[]DATA{type}[] []DATA{name}|camel[][]IF{DATA{optional}||| = 0}[]; // []NUMBER[] of []DATA{$.name}[]|||, }[]} []FOR_EACH_ITEM{tags|||[]DATA[]}[] []DATA{fields.1.name}[]";
+    let mut state = EvaluationState::new().with_data(Some(&data));
+    let result = replace_sub_symbols("template.h", test_string, &FileContext::blank(), None, &None, &Config::default(), &mut state);
+    assert_eq!("class Telemetry {float speed = 0; // 1 of Telemetry, int heading; // 2 of Telemetry} ab heading", result);
+    assert!(state.diagnostics.is_empty());
+
+    let mut state = EvaluationState::new().with_data(Some(&data));
+    let result = replace_sub_symbols("template.h", "[]DATA{fields.0.nmae}[] []FOR_EACH_ITEM{name|||x}[]", &FileContext::blank(), None, &None, &Config::default(), &mut state);
+    assert_eq!("ERR ERR", result);
+    assert_eq!(Some("name".to_string()), state.diagnostics[0].suggestion);
+    assert_eq!("name is not an array. ", state.diagnostics[1].message);
+
+    let mut state = EvaluationState::new();
+    replace_sub_symbols("template.h", "[]DATA{name}[]", &FileContext::blank(), None, &None, &Config::default(), &mut state);
+    assert!(state.diagnostics[0].message.contains("--data"));
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+
+use serde_json::Value;
+
+use crate::suggestion::did_you_mean;
+use crate::file_context::FileContext;
+
+// The file passed with --table, each row of it becomes its own output file. Tables can
+//  either be a CSV file with a header row or a JSON array of objects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<HashMap<String, String>>
+}
+
+impl Table {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let raw_table = read_to_string(path).map_err(|e| format!("Failed to load table {:}. {:}", path, e))?;
+
+        if path.to_lowercase().ends_with(".json") {
+            Self::from_json(&raw_table).map_err(|e| format!("Failed to read table {:}. {:}", path, e))
+        } else {
+            Self::from_csv(&raw_table).map_err(|e| format!("Failed to read table {:}. {:}", path, e))
+        }
+    }
+
+    pub fn from_csv(text: &str) -> Result<Self, String> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(text.as_bytes());
+
+        let columns: Vec<String> = reader.headers().map_err(|e| e.to_string())?.iter().map(|column| column.to_string()).collect();
+
+        let mut rows: Vec<HashMap<String, String>> = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|e| e.to_string())?;
+            rows.push(columns.iter().cloned().zip(record.iter().map(|value| value.to_string())).collect());
+        }
+
+        Ok(Self { columns, rows })
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let raw_rows: Vec<serde_json::Map<String, Value>> = serde_json::from_str(text)
+            .map_err(|e| format!("A JSON table should be an array of objects. {:}", e))?;
+
+        let mut columns: Vec<String> = Vec::new();
+        let mut rows: Vec<HashMap<String, String>> = Vec::new();
+        for raw_row in raw_rows {
+            let mut row: HashMap<String, String> = HashMap::new();
+            for (column, value) in raw_row {
+                let value = match value {
+                    Value::String(text) => text,
+                    Value::Null => String::new(),
+                    Value::Number(number) => number.to_string(),
+                    Value::Bool(boolean) => boolean.to_string(),
+                    _ => return Err(format!("Column {:} needs to be a string, number or boolean. ", column))
+                };
+
+                if !columns.contains(&column) {
+                    columns.push(column.clone());
+                }
+                row.insert(column, value);
+            }
+            rows.push(row);
+        }
+
+        Ok(Self { columns, rows })
+    }
+
+    // The column that output files are named after. Without one being asked for, a column
+    //  called "name" is used, and if there isn't one the first column is.
+    pub fn name_column(&self, requested_column: &Option<String>) -> Result<String, String> {
+        match requested_column {
+            Some(column) if self.columns.contains(column) => Ok(column.clone()),
+            Some(column) => {
+                let suggestion = match did_you_mean(column, &self.columns) {
+                    Some(suggestion) => format!(" Did you mean {:}?", suggestion),
+                    None => String::new()
+                };
+                Err(format!("The table has no column named {:}, it has {:}.{:}", column, self.columns.join(", "), suggestion))
+            },
+            None if self.columns.iter().any(|column| column == "name") => Ok("name".to_string()),
+            None => self.columns.first().cloned().ok_or("The table has no columns. ".to_string())
+        }
+    }
+
+    // One file per row, named after the name column and with every column as a template variable. 
+    //  Everything else, like the extension, comes from the file described on the command line. 
+    //  Two rows with the same name would write the same file, so that's an error too. 
+    pub fn file_contexts(&self, name_column: &str, description: &FileContext) -> Result<Vec<FileContext>, String> {
+        let mut file_contexts: Vec<FileContext> = Vec::new();
+        let mut named_rows: HashMap<String, usize> = HashMap::new();
+        for (index, row) in self.rows.iter().enumerate() {
+            let name = row.get(name_column).cloned().unwrap_or_default();
+            if name.is_empty() {
+                return Err(format!("Row {:} of the table has nothing in the {:} column to name its file with. ", index + 1, name_column));
+            }
+            if let Some(first_index) = named_rows.insert(name.clone(), index) {
+                return Err(format!("Rows {:} and {:} of the table both have {:} in the {:} column, so they would be written to the same file. ", first_index + 1, index + 1, name, name_column));
+            }
+
+            let mut file_context = description.clone();
+            file_context.name = name;
+            file_context.variables = row.clone();
+            file_contexts.push(file_context);
+        }
+
+        Ok(file_contexts)
+    }
+}
+
+#[test]
+fn csv_table_is_read_with_headers() {
+    let table = Table::from_csv("endpoint, method, path\nget_user, GET,\"/users/{id}, v2\"\ncreate_user,POST,/users\n").unwrap();
+
+    assert_eq!(vec!["endpoint", "method", "path"], table.columns);
+    assert_eq!(2, table.rows.len());
+    assert_eq!("/users/{id}, v2", table.rows[0]["path"]);
+    assert_eq!("POST", table.rows[1]["method"]);
+}
+
+#[test]
+fn json_table_is_read_from_objects() {
+    let table = Table::from_json(r#"[ { "name": "get_user", "port": 80 }, { "name": "create_user", "auth": true } ]"#).unwrap();
+
+    assert_eq!(vec!["name", "port", "auth"], table.columns);
+    assert_eq!("80", table.rows[0]["port"]);
+    assert_eq!("true", table.rows[1]["auth"]);
+    assert!(Table::from_json(r#"{ "name": "get_user" }"#).is_err());
+}
+
+#[test]
+fn name_column_defaults_to_name_then_first_column() {
+    let table = Table::from_csv("endpoint,name\na,b\n").unwrap();
+    assert_eq!(Ok("name".to_string()), table.name_column(&None));
+    assert_eq!(Ok("endpoint".to_string()), table.name_column(&Some("endpoint".to_string())));
+    assert!(table.name_column(&Some("endpiont".to_string())).unwrap_err().ends_with("Did you mean endpoint?"));
+
+    let table = Table::from_csv("endpoint,method\na,b\n").unwrap();
+    assert_eq!(Ok("endpoint".to_string()), table.name_column(&None));
+}
+
+#[test]
+fn every_row_becomes_a_file() {
+    let table = Table::from_csv("endpoint,method\nget_user,GET\ncreate_user,POST\n").unwrap();
+    let mut description = FileContext::blank();
+    description.name = "handler".to_string();
+    description.extension = "cpp".to_string();
+
+    let file_contexts = table.file_contexts("endpoint", &description).unwrap();
+    assert_eq!(2, file_contexts.len());
+    assert_eq!("get_user.cpp", file_contexts[0].name_with_extension());
+    assert_eq!("POST", file_contexts[1].variables["method"]);
+}
+
+#[test]
+fn rows_without_a_name_are_an_error() {
+    let table = Table::from_csv("endpoint,method\nget_user,GET\n,POST\n").unwrap();
+
+    assert_eq!(
+        Err("Row 2 of the table has nothing in the endpoint column to name its file with. ".to_string()), 
+        table.file_contexts("endpoint", &FileContext::blank()));
+}
+
+#[test]
+fn rows_with_the_same_name_are_an_error() {
+    let table = Table::from_csv("endpoint,method\nget_user,GET\ncreate_user,POST\nget_user,PUT\n").unwrap();
+
+    assert_eq!(
+        Err("Rows 1 and 3 of the table both have get_user in the endpoint column, so they would be written to the same file. ".to_string()), 
+        table.file_contexts("endpoint", &FileContext::blank()));
+}
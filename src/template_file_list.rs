@@ -1,9 +1,17 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs::read_dir;
+
 use crate::file_manip::*;
+use crate::parser::*;
+use crate::naming_pattern::NamingPattern;
+use crate::file_context::FileEnumeration;
+use crate::template_manifest::*;
 use crate::platform_specific::PLATFORM_SEPARATOR_SLASH;
 
 use log::*;
 
-const TEMPLATE_FILE_START: &str = "template.";
+pub const TEMPLATE_FILE_START: &str = "template.";
 
 // This const will come into play when the shove header functionality is implemented. 
 const HEADER_FILE_START: &str = "header.";
@@ -12,78 +20,374 @@ const HEADER_FILE_START: &str = "header.";
 pub struct TemplateFile {
     pub template_file_extension: String, 
     pub template_file_path: String, 
-    pub template_file_data: String
+    pub template_file_data: String,
+    pub template_tree: Vec<TemplateNode>,
+    pub naming_pattern: Option<NamingPattern>,
+    pub manifest: Option<TemplateManifest>
 }
 
 impl TemplateFile {
     pub fn new(
-        extension_list: &Vec<String>, 
+        extension_list: &[String], 
         root_path: &String) 
     -> Option<Self> {
 
         TemplateFile::generic_new(extension_list, root_path, TEMPLATE_FILE_START)
     }
 
-    pub fn new_header(extension_list: &Vec<String>, 
+    pub fn new_header(extension_list: &[String], 
         root_path: &String) 
     -> Option<Self> {
         TemplateFile::generic_new(extension_list, root_path, HEADER_FILE_START)
     }
 
+    // The template made for the file's enumeration values, ie: template.linux.cpp when making the linux version of
+    //  a cpp file. Gives back the extension of the template to use, which is the generic one if there isn't a more specific one. 
+    pub fn find_for_enumerations(
+        extension_list: &[String], 
+        root_path: &String, 
+        enumerations: &FileEnumeration) 
+    -> Option<String> {
+
+        let enumeration_values: Vec<String> = enumerations.values().cloned().collect();
+        figure_out_which_template_to_use(extension_list, root_path, TEMPLATE_FILE_START, &enumeration_values)
+    }
+
     fn generic_new(
-        extension_list: &Vec<String>, 
+        extension_list: &[String], 
         root_path: &String, 
         file_name_start: &str) 
     -> Option<Self> {
 
-        let found_template_file_matching_extension = figure_out_which_template_to_use(extension_list, root_path, file_name_start);
+        let found_template_file_matching_extension = figure_out_which_template_to_use(extension_list, root_path, file_name_start, &[])
+            .or_else(|| find_template_by_alias(extension_list, root_path, file_name_start));
         if found_template_file_matching_extension.is_none() {
             error!("Failed to find template file with any combo of extensions {:}", extension_list.join("."));
             return None;
         }
 
-        let template_file_extension    = found_template_file_matching_extension.unwrap();
-        let template_file_path: String = file_name_start.to_string() + &template_file_extension;
+        TemplateFile::load(&found_template_file_matching_extension.unwrap(), root_path, file_name_start)
+    }
+
+    pub fn load(
+        template_file_extension: &str, 
+        root_path: &String, 
+        file_name_start: &str) 
+    -> Option<Self> {
+
+        let template_file_path: String = file_name_start.to_string() + template_file_extension;
         let template_file_data         = load_file(root_path, &template_file_path);
         if template_file_data.is_none() {
             error!("Failed to load template file {:}", template_file_path);
             return None;
         }
 
+        let template_file_data = template_file_data.unwrap();
+        let full_template_file_path = root_path.clone() + PLATFORM_SEPARATOR_SLASH + &template_file_path;
+
+        let parsed_template = Parser::parse(&template_file_data);
+
+        let naming_pattern = find_naming_pattern(&parsed_template, &full_template_file_path);
+        if let Err(e) = naming_pattern {
+            error!("{:}", e);
+            return None;
+        }
+
+        // Templates that EXTEND another one get evaluated as their parent with the child's blocks swapped in. 
+        let template_tree = resolve_inheritance(
+            root_path,
+            &full_template_file_path,
+            &template_file_data,
+            parsed_template,
+            &mut Vec::new());
+        if let Err(e) = template_tree {
+            error!("{:}", e);
+            return None;
+        }
+
+        let manifest = TemplateManifest::load(&(full_template_file_path.clone() + MANIFEST_EXTENSION));
+        if let Err(e) = manifest {
+            error!("{:}", e);
+            return None;
+        }
+
         Some(Self {
-            template_file_extension: template_file_extension.clone(),
-            template_file_path: root_path.clone() + file_name_start + &template_file_extension,
-            template_file_data: template_file_data.unwrap()
+            manifest: manifest.unwrap(),
+            template_file_extension: template_file_extension.to_string(),
+            template_file_path: full_template_file_path,
+            template_tree: template_tree.unwrap(),
+            naming_pattern: naming_pattern.unwrap(),
+            template_file_data
         })
     }
 }
 
-pub fn figure_out_which_template_to_use(extension_list: &Vec<String>, root_path: &String, file_name_start: &str) -> Option<String> {
+// Follows the EXTENDS chain of a template up to the top most parent. What comes back is the
+//  parent's tree with the BLOCKs that this template overrides swapped out for this template's version. 
+//  Chain holds every template visited so far so that a template can't end up extending itself. 
+fn resolve_inheritance(
+    root_path: &String,
+    template_path: &str,
+    template_text: &str,
+    template_tree: Vec<TemplateNode>,
+    chain: &mut Vec<String>) 
+-> Result<Vec<TemplateNode>, String> {
+
+    chain.push(template_path.to_string());
+
+    let parent_name = match find_parent_template_name(&template_tree, template_path)? {
+        Some(parent_name) => parent_name,
+        None => return Ok(template_tree)
+    };
+
+    let parent_path = root_path.clone() + PLATFORM_SEPARATOR_SLASH + &parent_name;
+    if chain.contains(&parent_path) {
+        let template_names: Vec<&str> = chain.iter().chain(std::iter::once(&parent_path)).map(|path| template_name(path)).collect();
+        return Err(format!("Templates in {:} extend each other in a loop, {:}. ", root_path, template_names.join(" -> ")));
+    }
+
+    info!("Template {:} extends {:}", template_path, parent_path);
+
+    let parent_text = match load_file(root_path, &parent_name) {
+        Some(parent_text) => parent_text,
+        None => return Err(format!("Failed to load template {:} which {:} extends. ", parent_path, template_path))
+    };
+    let parent_tree = resolve_inheritance(root_path, &parent_path, &parent_text, Parser::parse(&parent_text), chain)?;
+
+    let block_overrides = find_block_overrides(&template_tree, template_path)?;
+
+    let mut overridden_blocks: HashSet<String> = HashSet::new();
+    let child_template = IncludedTemplate {
+        path: template_path.to_string(),
+        text: template_text.to_string(),
+        nodes: Vec::new()
+    };
+    let resolved_tree = override_blocks(parent_tree, &block_overrides, &child_template, &mut overridden_blocks);
+
+    for block_name in block_overrides.keys() {
+        if !overridden_blocks.contains(block_name) {
+            warn!("Template {:} overrides block {:}, but {:} has no block with that name. ", template_path, block_name, parent_path);
+        }
+    }
+
+    Ok(vec![TemplateNode::Included(IncludedTemplate {
+        path: parent_path,
+        text: parent_text,
+        nodes: resolved_tree
+    })])
+}
+
+fn template_name(template_path: &str) -> &str {
+    template_path.rsplit(PLATFORM_SEPARATOR_SLASH).next().unwrap_or(template_path)
+}
+
+// Only an EXTENDS at the top level of the template counts. 
+fn find_parent_template_name(template_tree: &[TemplateNode], template_path: &str) -> Result<Option<String>, String> {
+    let extends_tokens: Vec<&TokenNode> = top_level_tokens(template_tree, "EXTENDS");
+
+    match extends_tokens.as_slice() {
+        [] => Ok(None),
+        [extends] => match extends.parameters.first().and_then(|parameter| plain_text(parameter)) {
+            Some(parent_name) if !parent_name.trim().is_empty() => Ok(Some(parent_name.trim().to_string())),
+            _ => Err(format!("EXTENDS in {:} needs the file name of a template in the templates directory. ie: []EXTENDS{{template.h}}[]", template_path))
+        },
+        _ => Err(format!("Template {:} can only EXTEND one other template. ", template_path))
+    }
+}
+
+// The BLOCKs at the top level of a child template, anything else in a child template is ignored. 
+fn find_block_overrides(template_tree: &[TemplateNode], template_path: &str) -> Result<HashMap<String, Vec<TemplateNode>>, String> {
+    let mut block_overrides: HashMap<String, Vec<TemplateNode>> = HashMap::new();
+
+    for node in template_tree {
+        match node {
+            TemplateNode::Token(token) if token.id == "BLOCK" => {
+                let block_name = match token.parameters.first().and_then(|parameter| plain_text(parameter)) {
+                    Some(block_name) if !block_name.trim().is_empty() => block_name.trim().to_string(),
+                    _ => return Err(format!("BLOCK in {:} needs a name. ie: []BLOCK{{name|||text}}[]", template_path))
+                };
+
+                let block_text = token.parameters.get(1).cloned().unwrap_or_default();
+                if block_overrides.insert(block_name.clone(), block_text).is_some() {
+                    return Err(format!("Template {:} overrides block {:} more than once. ", template_path, block_name));
+                }
+            },
+            TemplateNode::Token(token) if token.id == "EXTENDS" => {},
+            TemplateNode::Text(text) if text.trim().is_empty() => {},
+            _ => warn!("Template {:} extends another template, everything outside of a BLOCK is ignored. ", template_path)
+        }
+    }
+
+    Ok(block_overrides)
+}
+
+// Blocks can be anywhere in the parent, including inside of another variable or a block the parent overrode. 
+fn override_blocks(
+    nodes: Vec<TemplateNode>, 
+    block_overrides: &HashMap<String, Vec<TemplateNode>>,
+    child_template: &IncludedTemplate,
+    overridden_blocks: &mut HashSet<String>) 
+-> Vec<TemplateNode> {
+
+    nodes.into_iter().map(|node| match node {
+        TemplateNode::Token(mut token) => {
+            let block_name = 
+                if token.id == "BLOCK" { token.parameters.first().and_then(|parameter| plain_text(parameter)) } 
+                else { None };
+
+            match block_name.and_then(|block_name| block_overrides.get_key_value(block_name.trim())) {
+                Some((block_name, block_text)) => {
+                    overridden_blocks.insert(block_name.clone());
 
-    let mut extension_list_copy = extension_list.clone();
+                    let overriding_text = vec![TemplateNode::Included(IncludedTemplate {
+                        nodes: block_text.clone(),
+                        ..child_template.clone()
+                    })];
 
-    let mut file_name = String::from(root_path) + PLATFORM_SEPARATOR_SLASH + file_name_start + &extension_list.join(".");
+                    token.parameters.truncate(1);
+                    token.parameters.push(overriding_text);
+                },
+                None => {
+                    token.parameters = token.parameters
+                        .into_iter()
+                        .map(|parameter| override_blocks(parameter, block_overrides, child_template, overridden_blocks))
+                        .collect();
+                }
+            }
 
-    info!("Checking to see if {:} exists", file_name);
-    
-    while !check_if_file_exists(&file_name) {
-        if extension_list_copy.len() == 1 {
+            TemplateNode::Token(token)
+        },
+        TemplateNode::Included(mut included) => {
+            included.nodes = override_blocks(included.nodes, block_overrides, child_template, overridden_blocks);
+            TemplateNode::Included(included)
+        },
+        other => other
+    }).collect()
+}
+
+fn top_level_tokens<'a>(template_tree: &'a [TemplateNode], id: &str) -> Vec<&'a TokenNode> {
+    template_tree
+        .iter()
+        .filter_map(|node| match node {
+            TemplateNode::Token(token) if token.id == id => Some(token),
+            _ => None
+        })
+        .collect()
+}
+
+// A template can name its files differently than the configuration says with a top level NAMING_PATTERN. 
+fn find_naming_pattern(template_tree: &[TemplateNode], template_path: &str) -> Result<Option<NamingPattern>, String> {
+    match top_level_tokens(template_tree, "NAMING_PATTERN")[..] {
+        [] => Ok(None),
+        [token] if token.parameters.len() == 1 => NamingPattern::from_nodes(&token.parameters[0])
+            .map(Some)
+            .map_err(|e| format!("Template {:} has a bad NAMING_PATTERN. {:}", template_path, e)),
+        [_] => Err(format!("NAMING_PATTERN in {:} needs exactly one pattern. ie: []NAMING_PATTERN{{[]NAME[].[]PLATFORM[]}}[]", template_path)),
+        _ => Err(format!("Template {:} has more than one NAMING_PATTERN. ", template_path))
+    }
+}
+
+// Names of blocks and parent templates have to be written out, they can't come from other variables. 
+fn plain_text(nodes: &[TemplateNode]) -> Option<String> {
+    let mut text = String::new();
+    for node in nodes {
+        match node {
+            TemplateNode::Text(node_text) => text += node_text,
+            _ => return None
+        }
+    }
+
+    Some(text)
+}
+
+// Looks for the most specific template there is. Leading parts of the extension list are dropped one at a time,
+//  ie: template.model.h then template.h, and for each of those the template with the most of the enumeration values
+//  in front of the extension is picked, ie: template.model.linux.fr.h over template.model.linux.h and template.model.fr.h
+pub fn figure_out_which_template_to_use(extension_list: &[String], root_path: &String, file_name_start: &str, enumeration_values: &[String]) -> Option<String> {
+
+    // The templates that are there are matched against the values, there are far too many combinations of values to try each one. 
+    let template_extensions: Vec<String> = match read_dir(root_path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| entry.file_name().to_str()?.strip_prefix(file_name_start).map(String::from))
+            .collect(),
+        Err(e) => {
+            info!("Failed to read the templates directory {:}. {:}", root_path, e);
+            return None;
+        }
+    };
+
+    let mut extension_list_copy = extension_list.to_vec();
+
+    loop {
+        info!("Checking to see if there is a template for {:}{:}", file_name_start, extension_list_copy.join("."));
+
+        // With the same number of values the template with values from earlier axes wins, ie: linux.h over fr.h
+        let most_specific_template = template_extensions
+            .iter()
+            .filter_map(|template_extension| Some((template_extension, enumeration_values_in_extension(&extension_list_copy, template_extension, enumeration_values)?)))
+            .min_by_key(|(_, value_indices)| (Reverse(value_indices.len()), value_indices.iter().rev().copied().collect::<Vec<usize>>()));
+
+        if let Some((template_extension, _)) = most_specific_template {
+            return Some(template_extension.clone());
+        }
+
+        if extension_list_copy.len() <= 1 {
             // No file exists, we just failed the file exists check. 
             return None;
         }
-    
+
         extension_list_copy.remove(0);
-        file_name = String::from(root_path) + PLATFORM_SEPARATOR_SLASH + file_name_start + &extension_list_copy.join(".");
-    
-        info!("Checking to see if {:} exists", file_name);
     }
-    
-    Some(extension_list_copy.join("."))
+}
+
+// A template whose manifest lists the extension as one of its aliases, ie: "aliases": ["cxx"] in template.cpp.manifest.json. 
+//  Leading extensions are dropped one at a time the same as they are when looking for the template itself. 
+fn find_template_by_alias(extension_list: &[String], root_path: &str, file_name_start: &str) -> Option<String> {
+    let template_files: Vec<(String, TemplateManifest)> = find_templates(root_path)
+        .into_iter()
+        .filter_map(|(name, manifest)| Some((name.strip_prefix(file_name_start)?.to_string(), manifest?)))
+        .collect();
+
+    (0..extension_list.len()).find_map(|first_extension| {
+        let extension = extension_list[first_extension..].join(".");
+        template_files
+            .iter()
+            .find(|(_, manifest)| manifest.has_alias(&extension))
+            .map(|(template_extension, _)| {
+                info!("Using template {:}{:} for {:}, it has {:} as an alias. ", file_name_start, template_extension, extension, extension);
+                template_extension.clone()
+            })
+    })
+}
+
+// Which of the enumeration values are right in front of the last extension of a template, ie: model.linux.h for model.h
+//  with [linux, fr] is [0]. The values are in the same order they were given in, None means the template isn't for the extension list. 
+fn enumeration_values_in_extension(extension_list: &[String], template_extension: &str, enumeration_values: &[String]) -> Option<Vec<usize>> {
+    let (last_extension, leading_extensions) = extension_list.split_last()?;
+    let template_parts: Vec<&str> = template_extension.split('.').collect();
+    let (template_last_extension, template_leading_parts) = template_parts.split_last()?;
+
+    if template_last_extension != last_extension 
+        || template_leading_parts.len() < leading_extensions.len() 
+        || !leading_extensions.iter().zip(template_leading_parts).all(|(extension, part)| extension == part) {
+        return None;
+    }
+
+    let mut value_indices = Vec::new();
+    let mut next_index = 0;
+    for part in &template_leading_parts[leading_extensions.len()..] {
+        let index = next_index + enumeration_values[next_index..].iter().position(|value| value == part)?;
+        value_indices.push(index);
+        next_index = index + 1;
+    }
+
+    Some(value_indices)
 }
 
 pub fn does_template_contain_forced_filename(template_data: &str) -> Option<String>{
 
-    use crate::parser::*;
     use crate::token::*;
 
     // If force_file_name is not the first token, we ignore it. 
@@ -104,4 +408,72 @@ pub fn does_template_contain_forced_filename(template_data: &str) -> Option<Stri
     let file_name = &token.variables.unwrap()[0].variable_list[0];
 
     Some(file_name.to_string())
-}
\ No newline at end of file
+}
+#[test]
+fn child_template_overrides_parent_blocks() {
+    use crate::config::Config;
+    use crate::file_context::FileContext;
+    use crate::symbol_replacer::replace_symbols;
+    use crate::prompt::Prompter;
+
+    let template_dir = std::env::temp_dir().join(format!("tt_extends_test_{:}", std::process::id()));
+    std::fs::create_dir_all(&template_dir).unwrap();
+    std::fs::write(template_dir.join("template.h"), "class []FILE_NAME_AS_TYPE[] {[]BLOCK{body|||}[]};[]BLOCK{footer||| // footer}[]").unwrap();
+    std::fs::write(template_dir.join("template.singleton.h"), "[]EXTENDS{template.h}[]\n[]BLOCK{body|||\n\tstatic []FILE_NAME_AS_TYPE[] & instance();[]BLOCK{members|||}[]\n}[]\n").unwrap();
+    std::fs::write(template_dir.join("template.counted.singleton.h"), "[]EXTENDS{template.singleton.h}[][]BLOCK{members|||\n\tint count;}[]").unwrap();
+    std::fs::write(template_dir.join("template.loop.h"), "[]EXTENDS{template.loop.h}[]").unwrap();
+    std::fs::write(template_dir.join("template.a.h"), "[]EXTENDS{template.b.h}[]").unwrap();
+    std::fs::write(template_dir.join("template.b.h"), "[]EXTENDS{template.c.h}[]").unwrap();
+    std::fs::write(template_dir.join("template.c.h"), "[]EXTENDS{template.a.h}[]").unwrap();
+    let root_path = template_dir.to_str().unwrap().to_string();
+
+    let mut file_context = FileContext::blank();
+    file_context.name = "settings".to_string();
+
+    let extension_list = |extensions: &str| extensions.split('.').map(|extension| extension.to_string()).collect::<Vec<String>>();
+
+    let singleton = TemplateFile::new(&extension_list("singleton.h"), &root_path).unwrap();
+    assert_eq!("class Settings {\n\tstatic Settings & instance();\n}; // footer", replace_symbols(&singleton, &file_context, &None, None, &Config::default(), &mut Prompter::non_interactive(HashMap::new())).text);
+
+    let counted_singleton = TemplateFile::new(&extension_list("counted.singleton.h"), &root_path).unwrap();
+    assert_eq!("class Settings {\n\tstatic Settings & instance();\n\tint count;\n}; // footer", replace_symbols(&counted_singleton, &file_context, &None, None, &Config::default(), &mut Prompter::non_interactive(HashMap::new())).text);
+
+    assert!(TemplateFile::new(&extension_list("loop.h"), &root_path).is_none());
+
+    let a_path = root_path.clone() + PLATFORM_SEPARATOR_SLASH + "template.a.h";
+    let a_text = "[]EXTENDS{template.b.h}[]";
+    assert_eq!(
+        Err(format!("Templates in {:} extend each other in a loop, template.a.h -> template.b.h -> template.c.h -> template.a.h. ", root_path)),
+        resolve_inheritance(&root_path, &a_path, a_text, Parser::parse(a_text), &mut Vec::new()));
+
+    let _ = std::fs::remove_dir_all(&template_dir);
+}
+
+#[test]
+fn enumeration_values_pick_the_most_specific_template() {
+    let template_dir = std::env::temp_dir().join(format!("tt_enumeration_template_test_{:}", std::process::id()));
+    std::fs::create_dir_all(&template_dir).unwrap();
+    for template_name in ["template.cpp", "template.linux.cpp", "template.linux.fr.cpp", "template.model.cpp", "template.model.windows.cpp"] {
+        std::fs::write(template_dir.join(template_name), "").unwrap();
+    }
+    let root_path = template_dir.to_str().unwrap().to_string();
+
+    let values = |values: &[&str]| values.iter().map(|value| value.to_string()).collect::<Vec<String>>();
+    let find = |extensions: &[&str], enumeration_values: &[&str]| 
+        figure_out_which_template_to_use(&values(extensions), &root_path, TEMPLATE_FILE_START, &values(enumeration_values));
+
+    assert_eq!(Some("cpp".to_string()), find(&["cpp"], &[]));
+    assert_eq!(Some("linux.fr.cpp".to_string()), find(&["cpp"], &["linux", "fr"]));
+    assert_eq!(Some("linux.cpp".to_string()), find(&["cpp"], &["linux", "de"]));
+    assert_eq!(Some("cpp".to_string()), find(&["cpp"], &["mac_os", "fr"]));
+    assert_eq!(Some("model.windows.cpp".to_string()), find(&["model", "cpp"], &["windows"]));
+    assert_eq!(Some("model.cpp".to_string()), find(&["model", "cpp"], &["linux"]));
+    assert_eq!(None, find(&["h"], &["linux"]));
+
+    // More values than there are bits in a usize. 
+    let mut many_values: Vec<String> = (0..100).map(|index| format!("value{:}", index)).collect();
+    many_values.push("linux".to_string());
+    assert_eq!(Some("linux.cpp".to_string()), figure_out_which_template_to_use(&values(&["cpp"]), &root_path, TEMPLATE_FILE_START, &many_values));
+
+    let _ = std::fs::remove_dir_all(&template_dir);
+}
@@ -0,0 +1,233 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{read_dir, read_to_string};
+use std::path::Path;
+
+use log::*;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::data::value_as_string;
+use crate::platform_specific::PLATFORM_SEPARATOR_SLASH;
+use crate::suggestion::did_you_mean;
+use crate::symbol_replacer::PARTIAL_DIRECTORY;
+
+// A template's manifest sits beside it with this added to its name, ie: template.cpp.manifest.json
+pub const MANIFEST_EXTENSION: &str = ".manifest.json";
+
+// A bundle's manifest is inside of the bundle's directory.
+pub const BUNDLE_MANIFEST: &str = "_manifest.json";
+
+// Describes a template or a bundle, everything in it is optional.
+//  description - What the template is for, shown by -z.
+//  variables   - USER_VAR variables the template needs with their type, and a default for when one isn't passed with --var.
+//  outputs     - When each file gets created, written the same as an IF condition, ie: { "file": "template.cpp", "if": "PLATFORM != windows" }
+//  aliases     - Other names the template can be used by, ie: cxx for template.cpp or qtc for the qt_component bundle.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateManifest {
+    #[serde(default)]
+    pub description: Option<String>,
+
+    #[serde(default)]
+    pub variables: BTreeMap<String, ManifestVariable>,
+
+    #[serde(default)]
+    pub outputs: Vec<ManifestOutput>,
+
+    #[serde(default)]
+    pub aliases: Vec<String>
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestVariable {
+    #[serde(default, rename = "type")]
+    pub variable_type: VariableType,
+
+    #[serde(default)]
+    pub default: Option<Value>,
+
+    #[serde(default)]
+    pub description: Option<String>
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VariableType {
+    #[default]
+    String,
+    Number,
+    Bool
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestOutput {
+    // The template's file name, or for a bundle the file's path in the bundle as it's written on disk.
+    pub file: String,
+
+    #[serde(rename = "if")]
+    pub condition: String
+}
+
+impl TemplateManifest {
+    // Templates don't need a manifest, so a missing one isn't an error.
+    pub fn load(path: &str) -> Result<Option<Self>, String> {
+        if !Path::new(path).is_file() {
+            return Ok(None);
+        }
+
+        let raw_manifest = read_to_string(path).map_err(|e| format!("Failed to load template manifest {:}. {:}", path, e))?;
+        serde_json::from_str(&raw_manifest)
+            .map(Some)
+            .map_err(|e| format!("Template manifest {:} is not valid. {:}", path, e))
+    }
+
+    // Fills in the defaults of variables that weren't passed, and checks that every variable has a value of the right type.
+    pub fn apply_variables(&self, user_variables: &mut HashMap<String, String>) -> Result<(), String> {
+        let mut problems: Vec<String> = Vec::new();
+        for (name, variable) in &self.variables {
+            let value = match (user_variables.get(name), &variable.default) {
+                (Some(value), _) => value.clone(),
+                (None, Some(default)) => value_as_string(default),
+                (None, None) => {
+                    let description = match &variable.description {
+                        Some(description) => format!(" ({:})", description),
+                        None => String::new()
+                    };
+                    problems.push(format!("The template needs {:}{:}, pass it with --var {:}=value. ", name, description, name));
+                    continue;
+                }
+            };
+
+            if !variable.variable_type.accepts(&value) {
+                problems.push(format!("{:} needs to be a {:}, got {:}. ", name, variable.variable_type.name(), value));
+                continue;
+            }
+
+            user_variables.insert(name.clone(), value);
+        }
+
+        if problems.is_empty() { Ok(()) } else { Err(problems.concat()) }
+    }
+
+    // Outputs can only be checked once we know which files the template has.
+    pub fn check_outputs(&self, template_names: &[String]) -> Result<(), String> {
+        for output in &self.outputs {
+            if !template_names.contains(&output.file) {
+                let suggestion = match did_you_mean(&output.file, template_names) {
+                    Some(suggestion) => format!(" Did you mean {:}?", suggestion),
+                    None => String::new()
+                };
+                return Err(format!("The manifest has an output for {:}, which isn't one of the template's files.{:}", output.file, suggestion));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn output_condition(&self, template_name: &str) -> Option<&str> {
+        self.outputs.iter().find(|output| output.file == template_name).map(|output| output.condition.as_str())
+    }
+
+    pub fn has_alias(&self, name: &str) -> bool {
+        self.aliases.iter().any(|alias| alias == name)
+    }
+}
+
+impl VariableType {
+    fn accepts(&self, value: &str) -> bool {
+        match self {
+            VariableType::String => true,
+            VariableType::Number => value.trim().parse::<f64>().is_ok(),
+            VariableType::Bool => value == "true" || value == "false"
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            VariableType::String => "string",
+            VariableType::Number => "number",
+            VariableType::Bool => "bool"
+        }
+    }
+}
+
+// Every template and bundle in the templates directory with its manifest, bundles are listed with a / after them.
+//  The partials directory isn't a bundle so it's left out.
+pub fn find_templates(template_dir_path: &str) -> Vec<(String, Option<TemplateManifest>)> {
+    let entries = match read_dir(template_dir_path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Failed to read the templates directory {:}. {:}", template_dir_path, e);
+            return Vec::new();
+        }
+    };
+
+    let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut templates: Vec<(String, Option<TemplateManifest>)> = Vec::new();
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = template_dir_path.to_string() + PLATFORM_SEPARATOR_SLASH + &name;
+
+        let (name, manifest_path) = if entry.path().is_dir() {
+            if name == PARTIAL_DIRECTORY {
+                continue;
+            }
+            (name + "/", path + PLATFORM_SEPARATOR_SLASH + BUNDLE_MANIFEST)
+        } else if name.ends_with(MANIFEST_EXTENSION) {
+            continue;
+        } else {
+            (name, path + MANIFEST_EXTENSION)
+        };
+
+        let manifest = TemplateManifest::load(&manifest_path).unwrap_or_else(|e| {
+            warn!("{:}", e);
+            None
+        });
+        templates.push((name, manifest));
+    }
+
+    templates
+}
+
+#[test]
+fn manifest_variables_get_defaults_and_are_checked() {
+    let manifest: TemplateManifest = serde_json::from_str(r#"{
+        "description": "A Qt component",
+        "variables": {
+            "BASE_CLASS": { "default": "QObject" },
+            "PORT": { "type": "number", "default": 8080 },
+            "WITH_QML": { "type": "bool", "default": false },
+            "MODULE": { "description": "The QML module the component is in" }
+        }
+    }"#).unwrap();
+
+    let mut user_variables: HashMap<String, String> = HashMap::new();
+    user_variables.insert("MODULE".to_string(), "Controls".to_string());
+    user_variables.insert("WITH_QML".to_string(), "true".to_string());
+    assert_eq!(Ok(()), manifest.apply_variables(&mut user_variables));
+    assert_eq!("QObject", user_variables["BASE_CLASS"]);
+    assert_eq!("8080", user_variables["PORT"]);
+    assert_eq!("true", user_variables["WITH_QML"]);
+
+    let mut user_variables: HashMap<String, String> = HashMap::new();
+    user_variables.insert("PORT".to_string(), "eighty".to_string());
+    assert_eq!(
+        Err("The template needs MODULE (The QML module the component is in), pass it with --var MODULE=value. PORT needs to be a number, got eighty. ".to_string()),
+        manifest.apply_variables(&mut user_variables));
+}
+
+#[test]
+fn manifest_outputs_must_be_template_files() {
+    let manifest: TemplateManifest = serde_json::from_str(r#"{ "outputs": [ { "file": "[]FILE_NAME_AS_TYPE[].qml", "if": "USER_VAR{WITH_QML} == true" } ], "aliases": [ "qtc" ] }"#).unwrap();
+
+    assert_eq!(Ok(()), manifest.check_outputs(&["[]FILE_NAME_AS_TYPE[].h".to_string(), "[]FILE_NAME_AS_TYPE[].qml".to_string()]));
+    assert!(manifest.check_outputs(&["[]FILE_NAME_AS_TYPE[].qm".to_string()]).unwrap_err().ends_with("Did you mean []FILE_NAME_AS_TYPE[].qm?"));
+    assert_eq!(Some("USER_VAR{WITH_QML} == true"), manifest.output_condition("[]FILE_NAME_AS_TYPE[].qml"));
+    assert!(manifest.has_alias("qtc"));
+
+    assert!(serde_json::from_str::<TemplateManifest>(r#"{ "descripton": "typo" }"#).is_err());
+}
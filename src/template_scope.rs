@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use log::*;
+use serde_json::Value;
+
+// Holds the variables a template defines for itself with DEFINE_TEMPLATE_VAR. Each
+//  FOR_EACH_FILE_IN_DIR, FOR_EACH_ITEM and REPEAT_X_TIMES iteration gets its own frame, so anything defined inside of
+//  the loop body goes away at the end of that iteration, while anything defined
+//  before the loop can still be used inside of it. FOR_EACH_ITEM frames also hold the item being looped over.
+#[derive(Debug, Clone)]
+pub struct TemplateScope {
+    frames: Vec<ScopeFrame>
+}
+
+#[derive(Debug, Clone)]
+struct ScopeFrame {
+    variables: HashMap<String, String>,
+    iteration: Option<LoopIteration>,
+    item: Option<Value>
+}
+
+// Where a loop is at, used by INDEX and NUMBER. 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct LoopIteration {
+    pub index: usize,
+    pub count: usize
+}
+
+impl TemplateScope {
+    pub fn new() -> Self {
+        Self {
+            frames: vec![ScopeFrame::new(None)]
+        }
+    }
+
+    // A frame that isn't a loop, used to give a partial its own arguments. 
+    pub fn push_frame(&mut self) {
+        self.frames.push(ScopeFrame::new(None));
+    }
+
+    pub fn push_iteration_frame(&mut self, iteration: LoopIteration) {
+        self.frames.push(ScopeFrame::new(Some(iteration)));
+    }
+
+    pub fn push_item_frame(&mut self, iteration: LoopIteration, item: Value) {
+        let mut frame = ScopeFrame::new(Some(iteration));
+        frame.item = Some(item);
+        self.frames.push(frame);
+    }
+
+    pub fn pop_frame(&mut self) {
+        if self.frames.len() == 1 {
+            error!("- INTERNAL - Attempted to pop the root frame of the template scope. ");
+            return;
+        }
+        self.frames.pop();
+    }
+
+    pub fn define(&mut self, name: &str, value: &str) {
+        info!("Defining template variable {:} as {:}", name, value);
+
+        self.frames.last_mut().unwrap().variables.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<String> {
+        self.frames.iter().rev().find_map(|frame| frame.variables.get(name).cloned())
+    }
+
+    // Every variable that can be seen from the current frame. 
+    pub fn names(&self) -> Vec<String> {
+        self.frames.iter().flat_map(|frame| frame.variables.keys().cloned()).collect()
+    }
+
+    // Variables defined outside of any loop or partial. 
+    pub fn root_variables(&self) -> HashMap<String, String> {
+        self.frames[0].variables.clone()
+    }
+
+    // The iteration of the inner most loop we're currently in. 
+    pub fn current_iteration(&self) -> Option<LoopIteration> {
+        self.frames.iter().rev().find_map(|frame| frame.iteration)
+    }
+
+    // The item of the inner most FOR_EACH_ITEM we're currently in. 
+    pub fn current_item(&self) -> Option<&Value> {
+        self.frames.iter().rev().find_map(|frame| frame.item.as_ref())
+    }
+}
+
+impl ScopeFrame {
+    fn new(iteration: Option<LoopIteration>) -> Self {
+        Self {
+            variables: HashMap::new(),
+            iteration,
+            item: None
+        }
+    }
+}
+
+#[test]
+fn inner_frame_sees_outer_variables() {
+    let mut scope = TemplateScope::new();
+    scope.define("NAME", "outer");
+    scope.push_iteration_frame(LoopIteration { index: 0, count: 1 });
+
+    assert_eq!(Some("outer".to_string()), scope.lookup("NAME"));
+}
+
+#[test]
+fn inner_frame_variables_go_away_when_popped() {
+    let mut scope = TemplateScope::new();
+    scope.define("NAME", "outer");
+
+    scope.push_iteration_frame(LoopIteration { index: 0, count: 1 });
+    scope.define("NAME", "inner");
+    scope.define("ONLY_INNER", "value");
+    assert_eq!(Some("inner".to_string()), scope.lookup("NAME"));
+
+    scope.pop_frame();
+    assert_eq!(Some("outer".to_string()), scope.lookup("NAME"));
+    assert_eq!(None, scope.lookup("ONLY_INNER"));
+}
+
+#[test]
+fn iteration_comes_from_inner_most_loop() {
+    let mut scope = TemplateScope::new();
+    assert_eq!(None, scope.current_iteration());
+
+    scope.push_iteration_frame(LoopIteration { index: 1, count: 3 });
+    assert_eq!(Some(LoopIteration { index: 1, count: 3 }), scope.current_iteration());
+
+    scope.push_iteration_frame(LoopIteration { index: 0, count: 2 });
+    assert_eq!(Some(LoopIteration { index: 0, count: 2 }), scope.current_iteration());
+
+    scope.pop_frame();
+    scope.pop_frame();
+    assert_eq!(None, scope.current_iteration());
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use log::*;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -14,16 +16,12 @@ pub struct TokenVariable {
 impl TokenVariable {
 	pub fn from_string(text: &str) -> Self {
 
-		let items: Vec<&str> = text.split(",").collect();
-		let mut formatted_items: Vec<String> = Vec::new();
-
-		for item in items {
-			if item == "" { continue; }
-			formatted_items.push(item.to_string());
-		}
+		// Empty items are kept so that rebuild_string gives back exactly what was written, 
+		//  lines like "A,B," need that trailing comma. 
+		let items: Vec<String> = text.split(',').map(|item| item.to_string()).collect();
 
 		Self {
-			variable_list: formatted_items
+			variable_list: items
 		}
 	}
 
@@ -65,59 +63,140 @@ impl Token {
 			return Vec::new();
 		}
 
-		self.variables.clone().unwrap()[index].variable_list.clone()
+		self.variables.clone().unwrap()[index].variable_list
+			.iter()
+			.map(|item| item.trim().to_string())
+			.filter(|item| !item.is_empty())
+			.collect()
 	}
 
-	pub fn from_string(text: &str) -> Result<Self, String> {
-		use regex::*;
+	pub fn from_string(text: &str) -> Result<Self, TokenParseError> {
+		Token::from_string_with_filters(text).map(|(token, _)| token)
+	}
 
-		let identifier_regex = Regex::new(r"[A-Z_]+").unwrap();
-		let variable_group_regex = Regex::new(r"\{((.|\s)*)\}").unwrap();
+	// Same as from_string, but also gives back the filters the token's value gets piped 
+	//  through, ie: []DIR|caps|+_H[] gives back DIR along with "caps" and "+_H". 
+	pub fn from_string_with_filters(text: &str) -> Result<(Self, Vec<String>), TokenParseError> {
 
-		let identifier_capture_groups: Vec<Captures> = identifier_regex.captures_iter(text).collect();
-		let variable_capture_groups: Vec<Captures> = variable_group_regex.captures_iter(text).collect();
+		// Tokens can be passed in with or without the [] around them. 
+		let inner_text = text.strip_prefix("[]").unwrap_or(text);
+		let inner_text = inner_text.strip_suffix("[]").unwrap_or(inner_text);
 
-		if identifier_capture_groups.is_empty() {
-			return Err(format!("Failed to parse identifier for line {:}", text));
+		let (inner_text, filters) = split_filters(inner_text);
+
+		let identifier_end = inner_text.find('{').unwrap_or(inner_text.len());
+		let identifier = inner_text[..identifier_end].trim();
+
+		if identifier.is_empty() {
+			return Err(TokenParseError::MissingIdentifier);
 		}
 
-		let identifier = (&identifier_capture_groups[0]).get(0).map_or("ERR", |i| i.as_str());
-		
-		let variable_group: Option<&str>;
-		if variable_capture_groups.is_empty() {
-			variable_group = None;
-		} else {
-			let variable_text = (&variable_capture_groups[0]).get(1).map_or("ERR", |v| v.as_str());
+		if !identifier.chars().all(|character| character.is_ascii_uppercase() || character == '_') {
+			return Err(TokenParseError::InvalidIdentifier(identifier.to_string()));
+		}
 
-			if variable_text == "" {
-				variable_group = None;
+		let variable_group: Option<&str> = 
+			if identifier_end == inner_text.len() {
+				None
+			} else if !inner_text.ends_with('}') {
+				return Err(TokenParseError::MalformedParameters(identifier.to_string()));
 			} else {
-				variable_group = Some(variable_text);
-			}
-		}
+				let variable_text = &inner_text[identifier_end + 1..inner_text.len() - 1];
+				if variable_text.is_empty() { None } else { Some(variable_text) }
+			};
 
 		info!("Identifier: {:}", identifier);
 		info!("Variable Group: {:?}", variable_group);
 
 		let mut variables: Vec<TokenVariable> = Vec::new();
-		if variable_group.is_some() {
-			let variable_group_text = variable_group.unwrap();
-
-			let parameter_list: Vec<&str> = variable_group_text.split("|||").collect();
-
-			for parameter in parameter_list {
+		if let Some(variable_group_text) = variable_group {
+			for parameter in split_parameters(variable_group_text) {
 				info!("Replacement Variable Found: {:?}", TokenVariable::from_string(parameter));
 				variables.push(TokenVariable::from_string(parameter))
 			}
 		}
 
-		Ok(Token {
+		if filters.iter().any(|filter| filter.is_empty()) {
+			return Err(TokenParseError::EmptyFilter(identifier.to_string()));
+		}
+
+		Ok((Token {
 			id: identifier.to_string(),
-			variables: if variables.len() == 0 { None } else { Some(variables) }
-		})
+			variables: if variables.is_empty() { None } else { Some(variables) }
+		}, filters))
+	}
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TokenParseError {
+	MissingIdentifier,
+	InvalidIdentifier(String),
+	MalformedParameters(String),
+	EmptyFilter(String)
+}
+
+impl fmt::Display for TokenParseError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			TokenParseError::MissingIdentifier => 
+				write!(f, "Token has no name. "),
+			TokenParseError::InvalidIdentifier(identifier) => 
+				write!(f, "{:} is not a valid token name, token names are made of capital letters and '_'. ", identifier),
+			TokenParseError::MalformedParameters(identifier) => 
+				write!(f, "The parameters of {:} need to end with }} right before the closing [] or the first filter. ", identifier),
+			TokenParseError::EmptyFilter(identifier) => 
+				write!(f, "{:} has an empty filter, each '|' needs to be followed by a formatting option. ie: []{:}|caps[]", identifier, identifier)
+		}
+	}
+}
+
+// Any '|' outside of the token's {} starts the filters, the "|||" between parameters is always inside of them.
+fn split_filters(text: &str) -> (&str, Vec<String>) {
+	let mut curly_bracket_depth: usize = 0;
+	for (index, character) in text.char_indices() {
+		match character {
+			'{' => curly_bracket_depth += 1,
+			'}' => curly_bracket_depth = curly_bracket_depth.saturating_sub(1),
+			'|' if curly_bracket_depth == 0 => {
+				let filters = text[index + 1..].split('|').map(|filter| filter.trim().to_string()).collect();
+				return (&text[..index], filters);
+			},
+			_ => {}
+		}
 	}
+
+	(text, Vec::new())
 }
 
+// Splits the parameter section of a token on "|||", but only at the top level. Any
+//  "|||" inside of a nested token's {} belongs to that token, this is what allows
+//  things like an IF inside of another IF's branch.
+fn split_parameters(text: &str) -> Vec<&str> {
+	let mut parameters: Vec<&str> = Vec::new();
+
+	let mut curly_bracket_depth: usize = 0;
+	let mut parameter_start: usize = 0;
+
+	let bytes = text.as_bytes();
+	let mut index: usize = 0;
+	while index < bytes.len() {
+		match bytes[index] {
+			b'{' => curly_bracket_depth += 1,
+			b'}' => curly_bracket_depth = curly_bracket_depth.saturating_sub(1),
+			b'|' if curly_bracket_depth == 0 && text[index..].starts_with("|||") => {
+				parameters.push(&text[parameter_start..index]);
+				index += 3;
+				parameter_start = index;
+				continue;
+			},
+			_ => {}
+		}
+		index += 1;
+	}
+	parameters.push(&text[parameter_start..]);
+
+	parameters
+}
 
 #[test]
 fn token_parse_without_variables() {
@@ -203,3 +282,60 @@ fn rebuild_string_single_variable() {
 
 	assert_eq!(Token::from_string(test_text).unwrap().variables.unwrap()[1].rebuild_string(), expected_result)
 }
+
+
+#[test]
+fn token_parse_with_nested_token_keeps_nested_parameters_together() {
+	let test_text = "IF{PLATFORM == windows|||[]IF{LANGUAGE == en|||a|||b}[]|||c}";
+
+	let token = Token::from_string(test_text).unwrap();
+
+	assert_eq!(token.variables.as_ref().unwrap().len(), 3);
+	assert_eq!(token.get_variable_as_string(1), "[]IF{LANGUAGE == en|||a|||b}[]");
+	assert_eq!(token.get_variable_as_string(2), "c");
+}
+
+#[test]
+fn rebuild_string_keeps_empty_items() {
+	let test_text = "REPEAT_X_TIMES{3|||A,,B,}";
+
+	let token = Token::from_string(test_text).unwrap();
+
+	assert_eq!(token.get_variable_as_string(1), "A,,B,");
+}
+
+#[test]
+fn variable_list_skips_empty_items() {
+	let test_text = "EACH_FILE_IN_DIR{h, ,cpp,}";
+
+	assert_eq!(Token::from_string(test_text).unwrap().get_variable_at(0), vec!["h".to_string(), "cpp".to_string()]);
+}
+
+#[test]
+fn token_parse_with_brackets() {
+	let token = Token::from_string("[]FILE_NAME{-_model}[]").unwrap();
+
+	assert_eq!(token.id, "FILE_NAME");
+	assert_eq!(token.get_variable_as_string(0), "-_model");
+}
+
+#[test]
+fn token_parse_errors() {
+	assert_eq!(Token::from_string("[][]"), Err(TokenParseError::MissingIdentifier));
+	assert_eq!(Token::from_string("[]file_name[]"), Err(TokenParseError::InvalidIdentifier("file_name".to_string())));
+	assert_eq!(Token::from_string("[]FILE_NAME{caps}x[]"), Err(TokenParseError::MalformedParameters("FILE_NAME".to_string())));
+}
+#[test]
+fn token_parse_with_filters() {
+	let (token, filters) = Token::from_string_with_filters("[]USER_VAR{TEAM}|kabob|+_H[]").unwrap();
+
+	assert_eq!(token.id, "USER_VAR");
+	assert_eq!(token.get_variable_as_string(0), "TEAM");
+	assert_eq!(filters, vec!["kabob".to_string(), "+_H".to_string()]);
+
+	let (token, filters) = Token::from_string_with_filters("[]IF{PLATFORM|||a|||b}|caps[]").unwrap();
+	assert_eq!(token.variables.unwrap().len(), 3);
+	assert_eq!(filters, vec!["caps".to_string()]);
+
+	assert_eq!(Token::from_string_with_filters("[]DIR||caps[]"), Err(TokenParseError::EmptyFilter("DIR".to_string())));
+}